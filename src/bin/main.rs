@@ -1,15 +1,39 @@
 use std::env;
+use std::io::{self, Read};
+use std::process;
+use text_editor::cli::{self, Args};
 use text_editor::config::EditorCfg;
-use text_editor::editor::Editor;
+use text_editor::editor::{self, Editor};
 
 fn main() {
-    let cfg: EditorCfg;
-    let args: Vec<String> = env::args().collect();
-    if args.len() >= 2 {
-        cfg = EditorCfg::new(args[1].clone());
-    } else {
-        cfg = EditorCfg::new(String::new());
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("my_vim: {}\nTry 'my_vim --help' for more information.", e);
+            process::exit(2);
+        }
+    };
+    if args.help {
+        print!("{}", cli::USAGE);
+        return;
+    }
+    if args.version {
+        println!("my_vim {}", editor::VERSION);
+        return;
     }
-    let mut editor = Editor::new(cfg);
-    editor.run();
+
+    // read before the terminal takes over, keys then come from the tty
+    let text = if args.stdin {
+        let mut text = Vec::new();
+        if let Err(e) = io::stdin().read_to_end(&mut text) {
+            eprintln!("my_vim: stdin: {}", e);
+            process::exit(1);
+        }
+        Some(text)
+    } else {
+        None
+    };
+
+    let status = Editor::new(EditorCfg::new()).run(args, text);
+    process::exit(status);
 }
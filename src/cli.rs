@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: my_vim [options] [+line] [file[:line[:col]]]...
+
+  +N              start on line N of the next file, + alone for its last line
+  file:L:C        start on line L, column C of file
+  -               read the text to edit from stdin
+  -R              read-only, :w! still writes
+  -c <command>    run an ex command after opening, may be repeated
+  --config <path> read settings from path instead of config.toml
+  -h, --help      show this help
+  -v, --version   show the version
+";
+
+/// A file to edit and where to put the cursor, 1-based.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FileArg {
+    pub name: String,
+    /// `u32::MAX` stands for the last line.
+    pub line: Option<u32>,
+    pub col: Option<u32>,
+}
+
+#[derive(Default, PartialEq, Debug)]
+pub struct Args {
+    pub files: Vec<FileArg>,
+    /// Edit the text read from stdin, given as `-`.
+    pub stdin: bool,
+    pub read_only: bool,
+    pub commands: Vec<String>,
+    pub config: Option<PathBuf>,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Args {
+    /// Parse the arguments after the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut line = None;
+        let mut options = true;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" if options => options = false,
+                "-h" | "--help" if options => parsed.help = true,
+                "-v" | "--version" if options => parsed.version = true,
+                "-R" if options => parsed.read_only = true,
+                "-c" if options => parsed.commands.push(args.next().ok_or("-c needs a command")?),
+                "--config" if options => parsed.config = Some(args.next().ok_or("--config needs a path")?.into()),
+                "-" => parsed.stdin = true,
+                "+" if options => line = Some(u32::MAX),
+                _ if options && arg.starts_with('+') => {
+                    line = Some(arg[1..].parse().ok().filter(|n| *n > 0).ok_or_else(|| format!("invalid line number '{}'", arg))?);
+                }
+                _ if options && arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+                _ => {
+                    let mut file = split_position(&arg);
+                    if line.is_some() {
+                        file.line = line.take();
+                        file.col = None;
+                    }
+                    parsed.files.push(file);
+                }
+            }
+        }
+        if line.is_some() {
+            return Err("+line needs a file after it".to_string());
+        }
+        if parsed.stdin && !parsed.files.is_empty() {
+            return Err("'-' can not be combined with file names".to_string());
+        }
+        Ok(parsed)
+    }
+}
+
+/// Split `file:line:col` as printed by compilers and grep, unless a file
+/// with the whole name exists.
+fn split_position(arg: &str) -> FileArg {
+    let whole = FileArg { name: arg.to_string(), line: None, col: None };
+    if Path::new(arg).exists() {
+        return whole;
+    }
+    let mut name = arg.strip_suffix(':').unwrap_or(arg);
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 {
+        match name.rsplit_once(':') {
+            Some((rest, n)) if !rest.is_empty() => match n.parse::<u32>() {
+                Ok(n) if n > 0 => {
+                    numbers.insert(0, n);
+                    name = rest;
+                }
+                _ => break,
+            },
+            _ => break,
+        }
+    }
+    if numbers.is_empty() {
+        return whole;
+    }
+    FileArg { name: name.to_string(), line: numbers.first().copied(), col: numbers.get(1).copied() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    fn file(name: &str, line: Option<u32>, col: Option<u32>) -> FileArg {
+        FileArg { name: name.to_string(), line, col }
+    }
+
+    #[test]
+    fn test_args() {
+        let args = parse(&["-R", "+42", "a.c", "b.c:7:3", "c.c:9:", "-c", "set ts=2", "--", "-d"]).unwrap();
+        assert!(args.read_only);
+        assert_eq!(vec!["set ts=2".to_string()], args.commands);
+        assert_eq!(vec![
+            file("a.c", Some(42), None),
+            file("b.c", Some(7), Some(3)),
+            file("c.c", Some(9), None),
+            file("-d", None, None),
+        ], args.files);
+
+        assert_eq!(vec![file("x:y", None, None), file("z", Some(u32::MAX), None)], parse(&["x:y", "+", "z"]).unwrap().files);
+        assert!(parse(&["-"]).unwrap().stdin);
+        assert!(parse(&["-", "a.c"]).is_err());
+        assert!(parse(&["+0", "a.c"]).is_err());
+        assert!(parse(&["a.c", "+3"]).is_err());
+        assert_eq!(vec![file("+3", None, None), file("+", None, None)], parse(&["--", "+3", "+"]).unwrap().files);
+        assert!(parse(&["-x"]).is_err());
+        assert!(parse(&["-c"]).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use std::env;
+use std::io::{self, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Where cut and copied text goes besides the editor's own clipboard.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClipboardMode {
+    /// The terminal through OSC 52, and a clipboard program when there is one,
+    /// pasting from that program.
+    Auto,
+    /// The terminal through OSC 52 only, which works over ssh.
+    Osc52,
+    /// Nowhere.
+    Internal,
+}
+
+const MODES: &[(ClipboardMode, &str)] = &[
+    (ClipboardMode::Auto, "auto"),
+    (ClipboardMode::Osc52, "osc52"),
+    (ClipboardMode::Internal, "internal"),
+];
+
+impl ClipboardMode {
+    pub fn name(self) -> &'static str {
+        MODES.iter().find(|(m, _)| *m == self).map(|(_, n)| *n).unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<ClipboardMode> {
+        MODES.iter().find(|(_, n)| *n == name).map(|(m, _)| *m)
+    }
+
+    pub fn names() -> String {
+        MODES.iter().map(|(_, n)| *n).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// How cut or copied text goes back in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClipKind {
+    /// At the cursor.
+    Chars,
+    /// As whole lines above the cursor's line.
+    Lines,
+    /// As a rectangle, a line on each row from the cursor's down, all at its column.
+    Block,
+}
+
+/// Cut or copied text, lines joined by `\n`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Clip {
+    pub text: Vec<u8>,
+    pub kind: ClipKind,
+}
+
+impl Clip {
+    /// The text as other programs get it, whole lines ending in a line break.
+    pub fn to_system(&self) -> Vec<u8> {
+        let mut text = self.text.clone();
+        if self.kind == ClipKind::Lines {
+            text.push(b'\n');
+        }
+        text
+    }
+}
+
+/// A program putting text on the desktop's clipboard and reading it back.
+pub struct Tool {
+    /// Only with this variable set, the display server it talks to.
+    pub env: &'static str,
+    pub copy: &'static [&'static str],
+    pub paste: &'static [&'static str],
+}
+
+const TOOLS: &[Tool] = &[
+    Tool { env: "WAYLAND_DISPLAY", copy: &["wl-copy"], paste: &["wl-paste", "--no-newline"] },
+    Tool { env: "DISPLAY", copy: &["xclip", "-selection", "clipboard"], paste: &["xclip", "-selection", "clipboard", "-o"] },
+    Tool { env: "DISPLAY", copy: &["xsel", "--clipboard", "--input"], paste: &["xsel", "--clipboard", "--output"] },
+    Tool { env: "", copy: &["pbcopy"], paste: &["pbpaste"] },
+];
+
+impl Tool {
+    /// The first clipboard program installed that can reach a display.
+    pub fn find() -> Option<&'static Tool> {
+        Tool::find_with(|var| env::var_os(var).is_some(), in_path)
+    }
+
+    /// The same, with `has_env` telling which variables are set and
+    /// `installed` which programs there are.
+    fn find_with(has_env: impl Fn(&str) -> bool, installed: impl Fn(&str) -> bool) -> Option<&'static Tool> {
+        TOOLS.iter().find(|tool| (tool.env.is_empty() || has_env(tool.env)) && installed(tool.copy[0]))
+    }
+
+    pub fn copy(&self, text: &[u8]) -> io::Result<()> {
+        let mut child = Command::new(self.copy[0]).args(&self.copy[1..])
+            .stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null())
+            .spawn()?;
+        // the programs keep the text from a child of their own, this one exits
+        let written = child.stdin.take().unwrap().write_all(text);
+        let status = child.wait()?;
+        written?;
+        if !status.success() {
+            return Err(io::Error::other(format!("{} failed: {}", self.copy[0], status)));
+        }
+        Ok(())
+    }
+
+    pub fn paste(&self) -> io::Result<Vec<u8>> {
+        let output = Command::new(self.paste[0]).args(&self.paste[1..]).stdin(Stdio::null()).stderr(Stdio::null()).output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!("{} failed: {}", self.paste[0], output.status)));
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// The escape sequence asking the terminal to put `text` on the clipboard.
+pub fn osc52(text: &[u8]) -> Vec<u8> {
+    format!("\x1b]52;c;{}\x07", base64(text)).into_bytes()
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0_u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|path| env::split_paths(&path).any(|dir| is_executable(&dir.join(program))))
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata().is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9vYmFy", base64(b"foobar"));
+        assert_eq!("w6k=", base64("é".as_bytes()));
+        assert_eq!("//79AA==", base64(b"\xff\xfe\xfd\x00"));
+        assert_eq!(b"\x1b]52;c;aGkK\x07".to_vec(), osc52(b"hi\n"));
+
+        let clip = Clip { text: b"a\nb".to_vec(), kind: ClipKind::Lines };
+        assert_eq!(b"a\nb\n".to_vec(), clip.to_system());
+    }
+
+    #[test]
+    fn test_tool_order() {
+        let find = |vars: &[&str], missing: &[&str]| {
+            Tool::find_with(|var| vars.contains(&var), |program| !missing.contains(&program)).map(|tool| tool.copy[0])
+        };
+        assert_eq!(Some("wl-copy"), find(&["WAYLAND_DISPLAY", "DISPLAY"], &[]));
+        assert_eq!(Some("xclip"), find(&["WAYLAND_DISPLAY", "DISPLAY"], &["wl-copy"]));
+        assert_eq!(Some("xclip"), find(&["DISPLAY"], &[]));
+        assert_eq!(Some("xsel"), find(&["DISPLAY"], &["xclip"]));
+        // pbcopy needs no display
+        assert_eq!(Some("pbcopy"), find(&["DISPLAY"], &["xclip", "xsel"]));
+        assert_eq!(Some("pbcopy"), find(&[], &[]));
+        assert_eq!(None, find(&["WAYLAND_DISPLAY"], &["wl-copy", "pbcopy"]));
+    }
+}
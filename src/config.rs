@@ -1,26 +1,461 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use termion::terminal_size;
+use crate::clipboard::ClipboardMode;
+use crate::highlight::Theme;
 
+#[cfg(test)]
+thread_local! {
+    /// Where a test's editor keeps its state, instead of the user's.
+    pub static TEST_STATE_DIR: std::cell::RefCell<Option<PathBuf>> = const { std::cell::RefCell::new(None) };
+}
+
+/// How long to wait for the rest of an escape sequence, in milliseconds.
+const ESC_TIMEOUT: u64 = 50;
+
+/// Short names accepted by `:set`, vim style.
+const ALIASES: &[(&str, &str)] = &[
+    ("ts", "tab_width"),
+    ("et", "expand_tabs"),
+    ("ai", "auto_indent"),
+    ("nu", "line_numbers"),
+    ("wrap", "soft_wrap"),
+    ("sw", "indent_width"),
+    ("ff", "line_ending"),
+    ("ro", "read_only"),
+    ("swf", "swap_file"),
+    ("aw", "autowrite"),
+    ("bk", "backup"),
+    ("bdir", "backup_dir"),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+const LINE_ENDINGS: &[(LineEnding, &str)] = &[(LineEnding::Lf, "lf"), (LineEnding::CrLf, "crlf"), (LineEnding::Cr, "cr")];
+
+impl LineEnding {
+    pub fn name(self) -> &'static str {
+        LINE_ENDINGS.iter().find(|(l, _)| *l == self).map(|(_, n)| *n).unwrap()
+    }
+
+    /// Also takes vim's `unix`, `dos` and `mac`.
+    pub fn from_name(name: &str) -> Option<LineEnding> {
+        let name = match name {
+            "unix" => "lf",
+            "dos" => "crlf",
+            "mac" => "cr",
+            name => name,
+        };
+        LINE_ENDINGS.iter().find(|(_, n)| *n == name).map(|(l, _)| *l)
+    }
+
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+            LineEnding::Cr => b"\r",
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct EditorCfg {
     pub screen_row: u32,
     pub screen_col: u32,
     pub file_name: String,
+    /// Settings file given with `--config`, instead of `config.toml`.
+    pub config_file: Option<PathBuf>,
+
+    /* settings, from the config file or `:set` */
+    pub tab_width: u32,
+    /// Columns one level of indentation takes, 0 uses `tab_width`.
+    pub indent_width: u32,
+    pub expand_tabs: bool,
+    /// Start new lines at the indentation of the one before, by the filetype's rules.
+    pub auto_indent: bool,
+    pub line_numbers: bool,
+    pub theme: Theme,
+    pub soft_wrap: bool,
+    pub quit_times: u8,
+    /// Seconds a status message stays up, 0 keeps it until the next one.
+    pub status_msg_timeout: u64,
+    pub esc_timeout: u64,
+    /// Draw on the terminal's alternate screen, leaving what was on it before untouched.
+    pub alt_screen: bool,
+    /// Where cut and copied text goes besides the editor.
+    pub clipboard: ClipboardMode,
+
+    /* how files are written */
+    pub line_ending: LineEnding,
+    pub final_newline: bool,
+    pub trim_trailing_whitespace: bool,
+    pub bom: bool,
+    /// Refuse to save, short of `:w!`.
+    pub read_only: bool,
+    /// Keep unsaved changes in a swap file to recover them after a crash.
+    pub swap_file: bool,
+    /// Seconds without input after which changes are saved, 0 never.
+    pub autosave: u64,
+    /// Save changes before switching to another file.
+    pub autowrite: bool,
+    /// Keep the file as it was before the first save, as `file~`.
+    pub backup: bool,
+    /// Keep timestamped copies there instead of `file~`, when set.
+    pub backup_dir: String,
+
+    /// `[filetype.<name>]` sections: filetype, setting name, value.
+    pub filetype_settings: Vec<(String, String, Value)>,
+}
+
+/// A setting value, as written in the config file or after `:set name=`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+impl Value {
+    /// Read a `:set` argument, where strings need no quotes.
+    pub fn parse_loose(s: &str) -> Value {
+        match Value::parse(s) {
+            Ok(v) => v,
+            Err(_) => Value::Str(s.to_string()),
+        }
+    }
+
+    /// Read a config file value: `true`/`false`, an integer or a quoted string.
+    pub fn parse(s: &str) -> Result<Value, String> {
+        let s = s.trim();
+        if s == "true" || s == "false" {
+            return Ok(Value::Bool(s == "true"));
+        }
+        if let Ok(n) = s.parse() {
+            return Ok(Value::Int(n));
+        }
+        let quoted = s.len() >= 2 && (s.starts_with('"') && s.ends_with('"') || s.starts_with('\'') && s.ends_with('\''));
+        if quoted {
+            return Ok(Value::Str(s[1..s.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\")));
+        }
+        Err(format!("invalid value '{}', expected true, false, a number or a quoted string", s))
+    }
+
+    fn as_bool(&self, name: &str) -> Result<bool, String> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(format!("{} must be true or false", name)),
+        }
+    }
+
+    fn as_int(&self, name: &str, range: RangeInclusive<i64>) -> Result<i64, String> {
+        match self {
+            Value::Int(n) if range.contains(n) => Ok(*n),
+            _ => Err(format!("{} must be a number from {} to {}", name, range.start(), range.end())),
+        }
+    }
+
+    fn as_str(&self, name: &str) -> Result<&str, String> {
+        match self {
+            Value::Str(s) => Ok(s),
+            _ => Err(format!("{} must be a string", name)),
+        }
+    }
+}
+
+impl Default for EditorCfg {
+    fn default() -> Self {
+        EditorCfg::new()
+    }
 }
 
 impl EditorCfg {
-    pub fn new(file_name: String) -> Self {
-        let size = terminal_size().unwrap();
+    pub fn new() -> Self {
+        // the usual size when there is no terminal to ask, in tests
+        let size = terminal_size().unwrap_or((80, 24));
         EditorCfg {
             screen_col: size.0 as u32,
             screen_row: (size.1 - 2) as u32,
-            file_name,
+            file_name: String::new(),
+            config_file: None,
+
+            tab_width: 4,
+            indent_width: 0,
+            expand_tabs: false,
+            auto_indent: true,
+            line_numbers: true,
+            theme: Theme::Default,
+            soft_wrap: false,
+            quit_times: 1,
+            status_msg_timeout: 5,
+            // same variable curses programs read
+            esc_timeout: env::var("ESCDELAY").ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(ESC_TIMEOUT),
+            alt_screen: true,
+            clipboard: ClipboardMode::Auto,
+
+            line_ending: LineEnding::Lf,
+            final_newline: true,
+            trim_trailing_whitespace: false,
+            bom: false,
+            read_only: false,
+            swap_file: true,
+            autosave: 0,
+            autowrite: false,
+            backup: false,
+            backup_dir: String::new(),
+
+            filetype_settings: Vec::new(),
         }
     }
 
     pub fn get_file_name(&self) -> &str {
-        if self.file_name == "" {
+        if self.file_name.is_empty() {
             "[No Name]"
         } else {
             &self.file_name
         }
     }
-}
\ No newline at end of file
+
+    /// Apply the `name = value` lines of a config file. Lines after a
+    /// `[filetype.<name>]` header are kept for files of that type. A missing
+    /// file is not an error; the first bad line is, reported with its line number.
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        let mut filetype: Option<String> = None;
+        for (n, line) in text.lines().enumerate() {
+            let err = |msg: String| format!("{}:{}: {}", path.display(), n + 1, msg);
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                match section.trim().strip_prefix("filetype.") {
+                    Some(name) if !name.is_empty() => filetype = Some(name.to_string()),
+                    _ => return Err(err(format!("unknown section '{}', expected [filetype.<name>]", section))),
+                }
+                continue;
+            }
+            let (name, value) = line.split_once('=').ok_or_else(|| err("expected 'name = value'".to_string()))?;
+            let (name, value) = (name.trim(), Value::parse(value).map_err(err)?);
+            match &filetype {
+                None => self.set(name, &value).map_err(err)?,
+                Some(filetype) => {
+                    // check it now, it is applied when a file of this type is opened
+                    self.clone().set(name, &value).map_err(err)?;
+                    self.filetype_settings.push((filetype.clone(), canonical_name(name).to_string(), value));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set(&mut self, name: &str, value: &Value) -> Result<(), String> {
+        let name = canonical_name(name);
+        match name {
+            "tab_width" => self.tab_width = value.as_int(name, 1..=16)? as u32,
+            "indent_width" => self.indent_width = value.as_int(name, 0..=16)? as u32,
+            "expand_tabs" => self.expand_tabs = value.as_bool(name)?,
+            "auto_indent" => self.auto_indent = value.as_bool(name)?,
+            "line_numbers" => self.line_numbers = value.as_bool(name)?,
+            "theme" => {
+                let theme = value.as_str(name)?;
+                self.theme = Theme::from_name(theme)
+                    .ok_or_else(|| format!("unknown theme '{}', expected one of {}", theme, Theme::names()))?;
+            }
+            "soft_wrap" => self.soft_wrap = value.as_bool(name)?,
+            "quit_times" => self.quit_times = value.as_int(name, 0..=10)? as u8,
+            "status_msg_timeout" => self.status_msg_timeout = value.as_int(name, 0..=3600)? as u64,
+            "esc_timeout" => self.esc_timeout = value.as_int(name, 0..=5000)? as u64,
+            "alt_screen" => self.alt_screen = value.as_bool(name)?,
+            "clipboard" => {
+                let mode = value.as_str(name)?;
+                self.clipboard = ClipboardMode::from_name(mode)
+                    .ok_or_else(|| format!("unknown clipboard '{}', expected one of {}", mode, ClipboardMode::names()))?;
+            }
+            "line_ending" => {
+                let ending = value.as_str(name)?;
+                self.line_ending = LineEnding::from_name(ending)
+                    .ok_or_else(|| format!("unknown line ending '{}', expected lf, crlf or cr", ending))?;
+            }
+            "final_newline" => self.final_newline = value.as_bool(name)?,
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = value.as_bool(name)?,
+            "bom" => self.bom = value.as_bool(name)?,
+            "read_only" => self.read_only = value.as_bool(name)?,
+            "swap_file" => self.swap_file = value.as_bool(name)?,
+            "autosave" => self.autosave = value.as_int(name, 0..=86400)? as u64,
+            "autowrite" => self.autowrite = value.as_bool(name)?,
+            "backup" => self.backup = value.as_bool(name)?,
+            "backup_dir" => self.backup_dir = value.as_str(name)?.to_string(),
+            _ => return Err(format!("unknown setting '{}'", name)),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<Value, String> {
+        let value = match canonical_name(name) {
+            "tab_width" => Value::Int(self.tab_width as i64),
+            "indent_width" => Value::Int(self.indent_width as i64),
+            "expand_tabs" => Value::Bool(self.expand_tabs),
+            "auto_indent" => Value::Bool(self.auto_indent),
+            "line_numbers" => Value::Bool(self.line_numbers),
+            "theme" => Value::Str(self.theme.name().to_string()),
+            "soft_wrap" => Value::Bool(self.soft_wrap),
+            "quit_times" => Value::Int(self.quit_times as i64),
+            "status_msg_timeout" => Value::Int(self.status_msg_timeout as i64),
+            "esc_timeout" => Value::Int(self.esc_timeout as i64),
+            "alt_screen" => Value::Bool(self.alt_screen),
+            "clipboard" => Value::Str(self.clipboard.name().to_string()),
+            "line_ending" => Value::Str(self.line_ending.name().to_string()),
+            "final_newline" => Value::Bool(self.final_newline),
+            "trim_trailing_whitespace" => Value::Bool(self.trim_trailing_whitespace),
+            "bom" => Value::Bool(self.bom),
+            "read_only" => Value::Bool(self.read_only),
+            "swap_file" => Value::Bool(self.swap_file),
+            "autosave" => Value::Int(self.autosave as i64),
+            "autowrite" => Value::Bool(self.autowrite),
+            "backup" => Value::Bool(self.backup),
+            "backup_dir" => Value::Str(self.backup_dir.clone()),
+            name => return Err(format!("unknown setting '{}'", name)),
+        };
+        Ok(value)
+    }
+
+    /// Columns one level of indentation takes.
+    pub fn indent(&self) -> u32 {
+        if self.indent_width == 0 { self.tab_width } else { self.indent_width }
+    }
+
+    /// What one level of indentation is written as.
+    pub fn indent_unit(&self) -> Vec<u8> {
+        if self.expand_tabs { vec![b' '; self.indent() as usize] } else { vec![b'\t'] }
+    }
+
+    /// The `[filetype.<name>]` settings for `filetype`, in file order.
+    pub fn settings_for(&self, filetype: &str) -> Vec<(String, Value)> {
+        self.filetype_settings.iter()
+            .filter(|(t, _, _)| t == filetype)
+            .map(|(_, name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
+
+/// Whether setting `name` belongs to the file being edited rather than the
+/// editor, so that `:set` leaves it alone for the next file.
+pub fn is_file_setting(name: &str) -> bool {
+    matches!(canonical_name(name), "line_ending" | "final_newline" | "bom" | "read_only")
+}
+
+/// The full name of setting `name`, which may be an alias such as `ff`.
+pub fn canonical_name(name: &str) -> &str {
+    ALIASES.iter().find(|(alias, _)| *alias == name).map(|(_, full)| *full).unwrap_or(name)
+}
+
+/// Cut a `#` comment off a line, leaving `#` inside quotes alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('#', None) => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Directory holding the editor's own config files, `$XDG_CONFIG_HOME/my_vim`
+/// or `~/.config/my_vim`.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("my_vim"))
+}
+
+/// Directory for files the editor keeps for itself, like swap files,
+/// `$XDG_STATE_HOME/my_vim` or `~/.local/state/my_vim`.
+pub fn state_dir() -> Option<PathBuf> {
+    #[cfg(test)]
+    if let Some(dir) = TEST_STATE_DIR.with(|dir| dir.borrow().clone()) {
+        return Some(dir);
+    }
+    let base = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(base.join("my_vim"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::TempDir;
+
+    #[test]
+    fn test_value_parse() {
+        assert_eq!(Ok(Value::Bool(true)), Value::parse(" true "));
+        assert_eq!(Ok(Value::Int(-3)), Value::parse("-3"));
+        assert_eq!(Ok(Value::Str("a \"b\" \\".to_string())), Value::parse(r#""a \"b\" \\""#));
+        assert_eq!(Ok(Value::Str("dark".to_string())), Value::parse("'dark'"));
+        assert!(Value::parse("dark").is_err());
+        assert!(Value::parse("\"").is_err());
+        assert_eq!(Value::Str("dark".to_string()), Value::parse_loose("dark"));
+        assert_eq!(Value::Int(8), Value::parse_loose("8"));
+    }
+
+    #[test]
+    fn test_set_get() {
+        let mut cfg = EditorCfg::new();
+        cfg.set("ts", &Value::Int(2)).unwrap();
+        assert_eq!(Ok(Value::Int(2)), cfg.get("tab_width"));
+        cfg.set("ff", &Value::Str("crlf".to_string())).unwrap();
+        assert_eq!(LineEnding::CrLf, cfg.line_ending);
+        assert_eq!(Ok(Value::Str("crlf".to_string())), cfg.get("line_ending"));
+
+        assert_eq!(Err("tab_width must be a number from 1 to 16".to_string()), cfg.set("ts", &Value::Int(0)));
+        assert!(cfg.set("expand_tabs", &Value::Int(1)).is_err());
+        assert!(cfg.set("theme", &Value::Str("nope".to_string())).is_err());
+        assert_eq!(Err("unknown setting 'nope'".to_string()), cfg.get("nope"));
+        assert_eq!(Ok(Value::Int(2)), cfg.get("ts"));
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = TempDir::new();
+        let path = dir.path.join("config.toml");
+        let mut cfg = EditorCfg::new();
+        assert_eq!(Ok(()), cfg.load(&path));
+
+        fs::write(&path, "tab_width = 2 # two\nbackup_dir = \"a#b\"\n\n[filetype.python]\nts = 4\n").unwrap();
+        cfg.load(&path).unwrap();
+        assert_eq!((2, "a#b"), (cfg.tab_width, cfg.backup_dir.as_str()));
+        assert_eq!(vec![("tab_width".to_string(), Value::Int(4))], cfg.settings_for("python"));
+
+        let error = |text: &str| {
+            fs::write(&path, text).unwrap();
+            EditorCfg::new().load(&path).unwrap_err()
+        };
+        let name = path.display();
+        assert_eq!(format!("{}:2: expected 'name = value'", name), error("ts = 2\nts\n"));
+        assert_eq!(format!("{}:1: unknown setting 'nope'", name), error("nope = 1\n"));
+        assert_eq!(format!("{}:1: unknown section 'python', expected [filetype.<name>]", name), error("[python]\n"));
+        assert_eq!(format!("{}:2: tab_width must be a number from 1 to 16", name), error("[filetype.c]\nts = 40\n"));
+        assert!(error("ts = two\n").starts_with(&format!("{}:1: invalid value", name)));
+    }
+}
@@ -0,0 +1,105 @@
+/// Past this many line pairs the changed middle of two texts is not
+/// aligned, only shown as removed and then added.
+const MAX_CELLS: usize = 1_000_000;
+
+#[derive(PartialEq, Debug)]
+pub enum Diff<'a> {
+    Same(&'a [u8]),
+    Removed(&'a [u8]),
+    Added(&'a [u8]),
+}
+
+/// Line diff turning `old` into `new`, by longest common subsequence.
+pub fn diff_lines<'a>(old: &[&'a [u8]], new: &[&'a [u8]]) -> Vec<Diff<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut diff: Vec<Diff> = old[..prefix].iter().map(|l| Diff::Same(l)).collect();
+    if a.len() * b.len() > MAX_CELLS {
+        diff.extend(a.iter().map(|l| Diff::Removed(l)));
+        diff.extend(b.iter().map(|l| Diff::Added(l)));
+    } else {
+        // lcs[i][j]: longest common subsequence of a[i..] and b[j..]
+        let width = b.len() + 1;
+        let mut lcs = vec![0_u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i * width + j] = if a[i] == b[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                diff.push(Diff::Same(a[i]));
+                i += 1;
+                j += 1;
+            } else if j == b.len() || i < a.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+                diff.push(Diff::Removed(a[i]));
+                i += 1;
+            } else {
+                diff.push(Diff::Added(b[j]));
+                j += 1;
+            }
+        }
+    }
+    diff.extend(old[old.len() - suffix..].iter().map(|l| Diff::Same(l)));
+    diff
+}
+
+/// The changes with `context` unchanged lines around them, as `-`, `+` and
+/// ` ` prefixed lines under an `@@ -line +line @@` header for each hunk.
+pub fn format_hunks(diff: &[Diff], context: usize) -> Vec<Vec<u8>> {
+    let changed: Vec<usize> = (0..diff.len()).filter(|i| !matches!(diff[*i], Diff::Same(_))).collect();
+    let shown = |i: usize| changed.iter().any(|c| c.abs_diff(i) <= context);
+
+    let mut lines = Vec::new();
+    let (mut old_no, mut new_no) = (1, 1);
+    let mut in_hunk = false;
+    for (i, d) in diff.iter().enumerate() {
+        if shown(i) {
+            if !in_hunk {
+                lines.push(format!("@@ -{} +{} @@", old_no, new_no).into_bytes());
+                in_hunk = true;
+            }
+            let (prefix, text) = match d {
+                Diff::Same(l) => (b' ', l),
+                Diff::Removed(l) => (b'-', l),
+                Diff::Added(l) => (b'+', l),
+            };
+            let mut line = vec![prefix];
+            line.extend_from_slice(text);
+            lines.push(line);
+        } else {
+            in_hunk = false;
+        }
+        match d {
+            Diff::Same(_) => (old_no, new_no) = (old_no + 1, new_no + 1),
+            Diff::Removed(_) => old_no += 1,
+            Diff::Added(_) => new_no += 1,
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old: Vec<&[u8]> = vec![b"a", b"b", b"c", b"d", b"e", b"f", b"g"];
+        let new: Vec<&[u8]> = vec![b"a", b"x", b"c", b"d", b"e", b"f", b"g", b"h"];
+        let diff = diff_lines(&old, &new);
+        assert_eq!(Diff::Removed(b"b"), diff[1]);
+        assert_eq!(Diff::Added(b"x"), diff[2]);
+        assert_eq!(9, diff.len());
+
+        let text: Vec<String> = format_hunks(&diff, 1).iter().map(|l| String::from_utf8_lossy(l).into_owned()).collect();
+        assert_eq!(vec!["@@ -1 +1 @@", " a", "-b", "+x", " c", "@@ -7 +7 @@", " g", "+h"], text);
+    }
+}
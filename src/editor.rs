@@ -1,26 +1,54 @@
 use std::cmp::{min};
 use std::fmt::{Arguments, format};
-use std::fs::File;
-use std::io::{BufRead, BufReader, Stdout, stdout, Write};
-use std::iter::repeat;
+use std::fs;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::time::{Duration, Instant};
 use memchr::memmem;
-use termion::raw::{IntoRawMode, RawTerminal};
-use crate::config::EditorCfg;
+use crate::cli::{Args, FileArg};
+use crate::registers::Registers;
+use crate::config::{EditorCfg, Value};
+use crate::event::EventLoop;
+use crate::fileio::FileStamp;
 use crate::highlight::Highlight;
-use crate::key::Keys;
+use self::events::Task;
+use self::selection::SelKind;
+use crate::key::{Event, Input, KeyCode, KeyEvent, Modifiers};
+use crate::keymap::{Command, Keymap, Lookup};
 use crate::syntax::Syntax;
-use crate::{syntax, util};
-use crate::util::get_current_time_secs;
-
-const VERSION: &str = "0.0.1";
-const TABLE_STOP: u8 = 4;
-const QUIT_TIMES: u8 = 1;
+use crate::terminal::{self, Terminal};
+use crate::watch::FileWatcher;
+use crate::{config, editorconfig, fileio, keymap, syntax, util};
+use crate::util::{get_current_time_secs, truncate_chars};
+
+mod autosave;
+mod brackets;
+mod clipboard;
+mod cursors;
+mod events;
+mod ex;
+mod external;
+mod files;
+mod indent;
+mod mouse;
+mod recovery;
+mod selection;
+
+pub const VERSION: &str = "0.0.1";
+/* width of the line number column */
+const GUTTER: u32 = 4;
 
 pub struct Editor {
-    stdout: RawTerminal<Stdout>,
+    stdout: Terminal,
+    events: EventLoop<Task>,
+    keymap: Keymap,
+    pending_keys: Vec<KeyEvent>,
 
     cx: u32,
     rx: u32,
+    /* the render column Up and Down keep to across short rows, while the cursor is where they left it */
+    want_rx: Option<(u32, (u32, u32))>,
     cy: u32,
     row_off: u32,
     col_off: u32,
@@ -38,25 +66,74 @@ pub struct Editor {
 
     last_match: i64,
     find_direction: i8,
+    last_search: String,
     saved_match_hl: Vec<Highlight>,
 
     syntax: Option<&'static Syntax>,
+    /* the file had more than one kind of line break, saving evens them out */
+    mixed_endings: bool,
+    /* line break, final newline and BOM as the file type or `.editorconfig`
+       give them, kept over what the text itself uses */
+    fixed_format: Vec<&'static str>,
+
+    /* the selection runs from the anchor (cx, cy) to the cursor */
+    sel_anchor: Option<(u32, u32)>,
+    sel_kind: SelKind,
+    /* visual mode: motions extend the selection instead of ending it */
+    visual: bool,
+    /* cursors besides the main one, as (cx, cy) in file order */
+    cursors: Vec<(u32, u32)>,
+    /* what was cut, copied and deleted, and the register picked for the next of those */
+    registers: Registers,
+    register: Option<char>,
+    last_click: Option<(Instant, u32, u32)>,
+
+    /* when the swap file was last written, None while there is none */
+    swap_written: Option<Instant>,
+    swap_keys: u32,
+
+    /* the file as last read or written, and a change to it already reported */
+    file_stamp: Option<FileStamp>,
+    ignored_stamp: Option<FileStamp>,
+    watcher: Option<FileWatcher>,
+    /* the file is to be checked once no prompt is open */
+    disk_check_pending: bool,
+
+    /* the file was copied to its backup this session */
+    backed_up: bool,
+    /* the signal telling the editor to stop, SIGHUP when the terminal is gone */
+    stop_signal: Option<i32>,
+
+    /* files named on the command line, edited one at a time */
+    files: Vec<FileArg>,
+    file_index: usize,
 
     cfg: EditorCfg,
+    /* settings before any file's own were applied */
+    user_cfg: EditorCfg,
 }
 
 /* pub func */
 impl Editor {
     pub fn new(cfg: EditorCfg) -> Self {
         // raw mode
-        let mut stdout = stdout().into_raw_mode().unwrap();
-        stdout.flush().unwrap();
-        // construct
+        Editor::with_terminal(cfg, Terminal::new().unwrap())
+    }
+}
+
+/* private func */
+impl Editor {
+    fn with_terminal(cfg: EditorCfg, stdout: Terminal) -> Self {
         Editor {
             stdout,
+            events: EventLoop::new(Input::new(Duration::from_millis(cfg.esc_timeout))).unwrap(),
+            keymap: Keymap::default(),
+            pending_keys: Vec::new(),
+            user_cfg: cfg.clone(),
             cfg,
 
             rx: 0,
+            want_rx: None,
             cx: 0,
             cy: 0,
             row_off: 0,
@@ -69,105 +146,337 @@ impl Editor {
             in_comment: Vec::new(),
 
             dirty: false,
-            quit_time: QUIT_TIMES,
+            quit_time: 0,
             status_msg: String::from(""),
             status_msg_time: 0,
 
             last_match: -1,
             find_direction: 1,
+            last_search: String::new(),
             saved_match_hl: Vec::new(),
 
             syntax: None,
+            mixed_endings: false,
+            fixed_format: Vec::new(),
+
+            sel_anchor: None,
+            sel_kind: SelKind::Char,
+            visual: false,
+            cursors: Vec::new(),
+            registers: Registers::default(),
+            register: None,
+            last_click: None,
+
+            swap_written: None,
+            swap_keys: 0,
+
+            file_stamp: None,
+            ignored_stamp: None,
+            watcher: None,
+            disk_check_pending: false,
+
+            backed_up: false,
+            stop_signal: None,
+
+            files: Vec::new(),
+            file_index: 0,
         }
     }
+}
 
-    pub fn run(&mut self) {
-        // let default_panic = std::panic::take_hook();
-        // std::panic::set_hook(Box::new(move |info| {
-        //     let mut stdout = stdout().into_raw_mode().unwrap();
-        //     stdout.flush().unwrap();
-        //     stdout.suspend_raw_mode().unwrap();
-        //     default_panic(info);
-        // }));
+/* pub func */
+impl Editor {
+    /// Edit the files in `args`, or `text` when it was read from stdin.
+    /// Returns the exit status: 0, or 128 plus the signal that stopped the editor.
+    pub fn run(&mut self, args: Args, text: Option<Vec<u8>>) -> i32 {
+        if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| self.edit(args, text))) {
+            // the panic hook gave the terminal back already, and said what went wrong
+            terminal::restore();
+            if let Some(msg) = self.emergency_dump() {
+                let _ = writeln!(io::stderr(), "my_vim: {}", msg);
+            }
+            panic::resume_unwind(panic);
+        }
+        self.stop_signal.map_or(0, |signal| 128 + signal)
+    }
+}
 
-        self.set_status_msg(format_args!("HELP: Ctrl-S = save | Ctrl-Q = quit | Ctrl-F = find"));
-        self.edit_or_open();
+/* private func */
+impl Editor {
+    fn edit(&mut self, args: Args, text: Option<Vec<u8>>) {
+        self.cfg.config_file = args.config;
+        match self.load_config().and_then(|_| self.load_keymap()) {
+            Ok(()) => {
+                let help = self.keymap.help_text();
+                self.set_status_msg(format_args!("{}", help));
+            }
+            Err(e) => self.set_status_msg(format_args!("{}", e)),
+        }
+        self.cfg.read_only |= args.read_only;
+        self.user_cfg = self.cfg.clone();
+        self.start_tasks();
+        self.load_registers();
+
+        self.files = args.files;
+        match text {
+            Some(text) => self.open_text(&text),
+            None => self.open_file(0),
+        }
+        for command in &args.commands {
+            if !self.ex_command(command) {
+                return;
+            }
+        }
 
         loop {
             self.refresh_screen();
             if !self.process_key_press() {
                 break;
             }
+            self.update_swap();
+            self.schedule_autosave();
+        }
+        if self.stop_signal.is_some() {
+            terminal::restore();
+            if let Some(msg) = self.emergency_dump() {
+                let _ = writeln!(io::stderr(), "my_vim: {}", msg);
+            }
+        } else {
+            self.remove_swap();
         }
     }
-}
 
-/* private func */
-impl Editor {
     /* process key */
     fn process_key_press(&mut self) -> bool {
-        let key = Keys::read_key();
-        match key {
-            Keys::QUIT => {
+        match self.wait_event(false) {
+            Some(event) => self.process_event(event),
+            None => false,
+        }
+    }
+
+    /* act on one event, returns false when the editor should exit */
+    fn process_event(&mut self, event: Event) -> bool {
+        let key = match event {
+            Event::Key(key) => key,
+            Event::Paste(text) => {
+                self.delete_selection();
+                self.edit_at_cursors(|e| e.insert_text(&text));
+                self.quit_time = self.cfg.quit_times;
+                return true;
+            }
+            Event::Mouse(mouse) => {
+                self.process_mouse(mouse);
+                return true;
+            }
+            Event::Focus(true) => {
+                self.check_disk();
+                return true;
+            }
+            Event::Focus(false) => return true,
+        };
+
+        self.pending_keys.push(key);
+        match self.keymap.lookup(&self.pending_keys) {
+            Lookup::Prefix => {
+                let keys = keymap::keys_to_string(&self.pending_keys);
+                self.set_status_msg(format_args!("{} -", keys));
+                true
+            }
+            Lookup::Command(command) => {
+                if self.pending_keys.len() > 1 {
+                    self.set_status_msg(format_args!(""));
+                }
+                self.pending_keys.clear();
+                if command.is_motion() && !self.visual {
+                    self.sel_anchor = None;
+                }
+                self.execute(command)
+            }
+            Lookup::Unbound => {
+                self.quit_time = self.cfg.quit_times;
+                let keys = std::mem::take(&mut self.pending_keys);
+                // Shift and a motion key select as they move, a block with Alt as well
+                if keys.len() == 1 && key.mods.contains(Modifiers::SHIFT) {
+                    let block = key.mods.contains(Modifiers::SHIFT | Modifiers::ALT);
+                    let unshifted = KeyEvent::new(key.code, key.mods.remove(Modifiers::SHIFT | Modifiers::ALT));
+                    if let Lookup::Command(command) = self.keymap.lookup(&[unshifted]) {
+                        if command.is_motion() {
+                            self.cursors.clear();
+                            self.start_selection(if block { SelKind::Block } else { SelKind::Char });
+                            return self.execute(command);
+                        }
+                    }
+                }
+                if keys.len() > 1 {
+                    self.set_status_msg(format_args!("{} is not bound", keymap::keys_to_string(&keys)));
+                } else if key.code == KeyCode::Esc {
+                    self.clear_selection();
+                    self.cursors.clear();
+                    self.register = None;
+                } else if let (true, KeyCode::Char(c)) = (keymap::is_self_insert(&key), key.code) {
+                    // typing replaces the selection, a block's on every row
+                    if !self.change_block() {
+                        self.delete_selection();
+                    }
+                    self.register = None;
+                    let mut buf = [0; 4];
+                    let bytes = c.encode_utf8(&mut buf).as_bytes();
+                    self.edit_at_cursors(|e| {
+                        e.dedent_for(bytes[0]);
+                        for b in bytes {
+                            e.insert_char(*b);
+                        }
+                    });
+                }
+                true
+            }
+        }
+    }
+
+    /* run a command, returns false when the editor should exit */
+    fn execute(&mut self, command: Command) -> bool {
+        if command != Command::Quit {
+            self.quit_time = self.cfg.quit_times;
+        }
+        if !command.keeps_selection() {
+            self.clear_selection();
+        }
+        if !command.keeps_register() {
+            self.register = None;
+        }
+        if !command.keeps_cursors() {
+            self.cursors.clear();
+        }
+        if command.is_motion() && !self.cursors.is_empty() {
+            self.move_cursors(|e| {
+                e.execute(command);
+            });
+            return true;
+        }
+        match command {
+            Command::Quit => {
                 if self.dirty && self.quit_time > 0 {
                     let q = self.quit_time;
+                    let keys = self.keymap.keys_for(Command::Quit).unwrap_or_default();
                     self.set_status_msg(format_args!("WARNING!!! File has unsaved changes. \
-                    Press Ctrl-Q {} more times to quit.", q));
+                    Press {} {} more times to quit.", keys, q));
                     self.quit_time = self.quit_time.saturating_sub(1);
                     return true;
                 }
-                print!("\x1b[2J\x1b[H");
                 return false;
             }
-            Keys::ENTER => self.insert_new_line(),
-            Keys::CTL_S => self.save_file(),
-            Keys::CTL_F => self.find_world(),
-            Keys::PAGE_UP => {
+            Command::Save => self.save_file(false),
+            Command::Find => self.find_world(),
+            Command::Redraw => {}
+            Command::Suspend => self.suspend(),
+            Command::NewLine => {
+                self.delete_selection();
+                self.edit_at_cursors(Self::insert_new_line);
+            }
+            Command::DeleteBackward => {
+                if !self.delete_selected() {
+                    // a level of indentation at a time
+                    self.edit_at_cursors(|e| if !e.delete_indent_level() { e.delete_char() });
+                }
+            }
+            Command::DeleteForward => {
+                if !self.delete_selected() {
+                    self.edit_at_cursors(|e| {
+                        if e.cy < e.rows_num && (e.cx as usize) < e.row[e.cy as usize].len() || e.cy + 1 < e.rows_num {
+                            e.move_cursor(KeyCode::Right);
+                            e.delete_char();
+                        }
+                    });
+                }
+            }
+            Command::InsertTab => {
+                if self.indent_selection() {
+                    return true;
+                }
+                self.clear_selection();
+                self.edit_at_cursors(|e| {
+                    if e.cfg.expand_tabs {
+                        e.row_cx_to_rx();
+                        let tab_width = e.cfg.tab_width;
+                        for _ in 0..tab_width - e.rx % tab_width {
+                            e.insert_char(b' ');
+                        }
+                    } else {
+                        e.insert_char(b'\t');
+                    }
+                });
+            }
+            Command::Indent => self.shift_lines_at_cursors(false),
+            Command::Outdent => self.shift_lines_at_cursors(true),
+            Command::Execute => return self.command_line(),
+            Command::Visual => self.toggle_visual(SelKind::Char),
+            Command::VisualLine => self.toggle_visual(SelKind::Line),
+            Command::VisualBlock => self.toggle_visual(SelKind::Block),
+            Command::BlockInsert => self.block_cursors(false),
+            Command::BlockAppend => self.block_cursors(true),
+            Command::UpperCase => self.change_case(true),
+            Command::LowerCase => self.change_case(false),
+            Command::Cut => self.cut(),
+            Command::Copy => self.copy(),
+            Command::Paste => self.paste(),
+            Command::Register => self.select_register(),
+            Command::AddCursorNext => self.add_cursor_next_word(),
+            Command::AddCursorAbove => self.add_cursor_vertical(false),
+            Command::AddCursorBelow => self.add_cursor_vertical(true),
+            Command::CursorsAtMatches => self.add_cursors_at_matches(),
+            Command::MoveLeft => self.move_cursor(KeyCode::Left),
+            Command::MoveRight => self.move_cursor(KeyCode::Right),
+            Command::MoveUp => self.move_cursor(KeyCode::Up),
+            Command::MoveDown => self.move_cursor(KeyCode::Down),
+            Command::WordLeft => self.move_word(KeyCode::Left),
+            Command::WordRight => self.move_word(KeyCode::Right),
+            Command::LineStart => self.cx = 0,
+            Command::LineEnd => {
+                if self.cy < self.rows_num {
+                    self.cx = self.row[self.cy as usize].len() as u32;
+                }
+            }
+            Command::MatchBracket => self.jump_to_match(),
+            Command::PageUp => {
                 self.cy = self.row_off;
                 let mut times = self.cfg.screen_row;
                 while times > 0 {
-                    self.move_cursor(Keys::ARROW_UP);
+                    self.move_cursor(KeyCode::Up);
                     times -= 1;
                 }
             }
-            Keys::PAGE_DOWN => {
+            Command::PageDown => {
                 self.cy = self.row_off + self.cfg.screen_row - 1;
                 if self.cy > self.rows_num {
                     self.cy = self.rows_num;
                 }
                 let mut times = self.cfg.screen_row;
                 while times > 0 {
-                    self.move_cursor(Keys::ARROW_DOWN);
+                    self.move_cursor(KeyCode::Down);
                     times -= 1;
                 }
             }
-            Keys::HOME_KEY => self.cx = 0,
-            Keys::END_KEY => {
-                if self.cy < self.rows_num {
-                    self.cx = self.row[self.cy as usize].len() as u32;
-                }
-            }
-            Keys::BACKSPACE | Keys::CTL_H | Keys::DEL_KEY => self.delete_char(),
-            Keys::ARROW_UP | Keys::ARROW_DOWN | Keys::ARROW_LEFT | Keys::ARROW_RIGHT => self.move_cursor(key),
-            Keys::CTL_L | Keys::ESC => {}
-            Keys::NORMAL(k) => self.insert_char(k),
-        };
-        self.quit_time = QUIT_TIMES;
-        return true;
+        }
+        true
     }
 
-    fn move_cursor(&mut self, key: Keys) {
+    fn move_cursor(&mut self, key: KeyCode) {
         match key {
-            // up move
-            Keys::ARROW_UP => {
-                self.cy = self.cy.saturating_sub(1);
-            }
-            // down move
-            Keys::ARROW_DOWN => {
-                self.cy = min(self.cy.wrapping_add(1), self.rows_num - 1);
+            // up and down move, to the same column
+            KeyCode::Up | KeyCode::Down => {
+                let rx = self.want_rx();
+                if key == KeyCode::Up {
+                    self.cy = self.cy.saturating_sub(1);
+                } else {
+                    self.cy = min(self.cy.wrapping_add(1), self.rows_num.saturating_sub(1));
+                }
+                if self.cy < self.rows_num {
+                    self.cx = self.rx_to_cx(self.cy as usize, rx);
+                }
+                self.want_rx = Some((rx, (self.cx, self.cy)));
             }
             // left move
-            Keys::ARROW_LEFT => {
+            KeyCode::Left => {
                 if self.cx > 0 {
                     self.cx -= 1;
                 } else if self.cy > 0 { // move to right at the end of a line
@@ -177,14 +486,12 @@ impl Editor {
                     }
                 }
             }
-            Keys::ARROW_RIGHT => {
-                if self.cy < self.rows_num {
-                    if self.cx < self.row[self.cy as usize].len() as u32 {
-                        self.cx = self.cx.wrapping_add(1)
-                    } else if self.cy < self.rows_num - 1 { // move to left at the next of a line
-                        self.cx = 0;
-                        self.cy += 1;
-                    }
+            KeyCode::Right if self.cy < self.rows_num => {
+                if self.cx < self.row[self.cy as usize].len() as u32 {
+                    self.cx = self.cx.wrapping_add(1)
+                } else if self.cy < self.rows_num - 1 { // move to left at the next of a line
+                    self.cx = 0;
+                    self.cy += 1;
                 }
             }
             _ => {}
@@ -198,34 +505,90 @@ impl Editor {
         }
     }
 
+    /* the column to keep to moving up or down: the one the last such move wanted if the cursor stayed put */
+    fn want_rx(&self) -> u32 {
+        match self.want_rx {
+            Some((rx, pos)) if pos == (self.cx, self.cy) => rx,
+            _ if self.cy < self.rows_num => self.cx_to_rx(self.cy as usize, self.cx),
+            _ => 0,
+        }
+    }
+
+    /* move over a whole word, like Ctrl-Left/Right elsewhere */
+    fn move_word(&mut self, key: KeyCode) {
+        let is_word = util::is_word_char;
+        let at = |s: &Self| -> Option<u8> {
+            let line = s.row.get(s.cy as usize)?;
+            match key {
+                KeyCode::Left if s.cx > 0 => Some(line[s.cx as usize - 1]),
+                KeyCode::Right => line.get(s.cx as usize).copied(),
+                _ => None,
+            }
+        };
+
+        // skip separators, crossing line boundaries, then the word itself
+        loop {
+            match at(self) {
+                Some(c) if is_word(c) => break,
+                Some(_) => self.move_cursor(key),
+                None => {
+                    let (cx, cy) = (self.cx, self.cy);
+                    self.move_cursor(key);
+                    if (cx, cy) == (self.cx, self.cy) { return; }
+                }
+            }
+        }
+        while matches!(at(self), Some(c) if is_word(c)) {
+            self.move_cursor(key);
+        }
+    }
+
     /* promotion read for status bar*/
     fn promotion_read<F>(&mut self, s: String, callback: F) -> String
         where
-            F: Fn(&mut Self, &Keys, &str),
+            F: Fn(&mut Self, &KeyEvent, &str),
     {
         let mut user_input = String::new();
         loop {
             self.set_status_msg(format_args!("{}", &s.replace("{}", &user_input)));
             self.refresh_screen();
 
-            let key = Keys::read_key();
-            match key {
-                Keys::BACKSPACE | Keys::DEL_KEY | Keys::CTL_H => {
-                    if user_input.len() != 0 {
-                        user_input.remove(user_input.len() - 1);
+            // a terminal gone away cancels
+            let key = match self.wait_event(true).unwrap_or(Event::Key(KeyEvent::plain(KeyCode::Esc))) {
+                Event::Key(key) => key,
+                // typed in as if it came from the keyboard, up to the first line break
+                Event::Mouse(_) | Event::Focus(_) => continue,
+                Event::Paste(text) => {
+                    let text = String::from_utf8_lossy(&text);
+                    let line = text.lines().next().unwrap_or("");
+                    let before = user_input.len();
+                    user_input.extend(line.chars().filter(|c| c.is_ascii() && !c.is_ascii_control()));
+                    // the callback sees the last character pasted, as if it was just typed
+                    if let Some(c) = user_input[before..].chars().last() {
+                        callback(self, &KeyEvent::plain(KeyCode::Char(c)), &user_input);
                     }
+                    continue;
                 }
-                Keys::ESC => {
+            };
+            match key.code {
+                KeyCode::Backspace | KeyCode::Delete => {
+                    user_input.pop();
+                }
+                KeyCode::Char('h') if key.mods == Modifiers::CTRL => {
+                    user_input.pop();
+                }
+                KeyCode::Esc => {
                     self.set_status_msg(format_args!(""));
                     callback(self, &key, &user_input);
                     return String::new();
                 }
-                Keys::ENTER => {
+                KeyCode::Enter => {
                     self.set_status_msg(format_args!(""));
                     callback(self, &key, &user_input);
                     break;
                 }
-                Keys::NORMAL(c) if !c.is_ascii_control() && c < 128 => user_input.push(c as char),
+                KeyCode::Char(c) if !key.mods.intersects(Modifiers::CTRL | Modifiers::ALT) && c.is_ascii()
+                    && !c.is_ascii_control() => user_input.push(c),
                 _ => {}
             }
             callback(self, &key, &user_input);
@@ -240,16 +603,19 @@ impl Editor {
         self.stdout.write_all(b"\x1b[?25l").unwrap();
         self.stdout.write_all(b"\x1b[H").unwrap();
 
-        self.draw_rows();
+        let lines = self.screen_lines();
+        let bracket = self.shown_match();
+        self.draw_rows(&lines, bracket);
         self.draw_status_bar();
         self.draw_status_msg();
 
-        if self.rows_num == 0 {
-            self.stdout.write_all(b"\x1b[H").unwrap();
-        } else {
-            self.stdout.write_all(
-                format!("\x1b[{};{}H", self.cy - self.row_off + 1, self.rx - self.col_off + 5).as_bytes()
-            ).unwrap();
+        let sub = self.cursor_sub_row();
+        match lines.iter().position(|l| *l == Some((self.cy, sub))) {
+            Some(r) if self.rows_num > 0 => {
+                let col = self.rx - self.segment_start(sub) + self.gutter_width() + 1;
+                self.stdout.write_all(format!("\x1b[{};{}H", r + 1, col).as_bytes()).unwrap();
+            }
+            _ => self.stdout.write_all(b"\x1b[H").unwrap(),
         }
         self.stdout.write_all(b"\x1b[?25h").unwrap();
 
@@ -263,6 +629,16 @@ impl Editor {
             self.row_off = self.cy;
         }
 
+        if self.cfg.soft_wrap {
+            // whole rows scroll off the top until the cursor's part of its row fits
+            self.col_off = 0;
+            let needed = |s: &Self| (s.row_off..s.cy).map(|y| s.wrapped_rows(y)).sum::<u32>() + s.cursor_sub_row() + 1;
+            while self.row_off < self.cy && needed(self) > self.cfg.screen_row {
+                self.row_off += 1;
+            }
+            return;
+        }
+
         if self.cy >= self.row_off + self.cfg.screen_row {
             self.row_off = self.cy - self.cfg.screen_row + 1;
         }
@@ -271,22 +647,65 @@ impl Editor {
             self.col_off = self.rx;
         }
 
-        if self.rx >= self.col_off + self.cfg.screen_col {
-            self.col_off = self.rx - self.cfg.screen_col + 1;
+        if self.rx >= self.col_off + self.text_width() {
+            self.col_off = self.rx - self.text_width() + 1;
+        }
+    }
+
+    /* the file row and the wrapped part of it shown on each screen line */
+    fn screen_lines(&self) -> Vec<Option<(u32, u32)>> {
+        let mut lines = Vec::new();
+        let mut y = self.row_off;
+        while lines.len() < self.cfg.screen_row as usize {
+            if y >= self.rows_num {
+                lines.push(None);
+                continue;
+            }
+            for sub in 0..self.wrapped_rows(y) {
+                lines.push(Some((y, sub)));
+            }
+            y += 1;
+        }
+        lines.truncate(self.cfg.screen_row as usize);
+        lines
+    }
+
+    /* screen lines taken by file row `y` */
+    fn wrapped_rows(&self, y: u32) -> u32 {
+        if !self.cfg.soft_wrap || y >= self.rows_num {
+            return 1;
         }
+        let width = self.text_width().max(1);
+        (self.render[y as usize].len() as u32).div_ceil(width).max(1)
     }
 
-    fn draw_rows(&mut self) {
-        for r in 0..self.cfg.screen_row {
-            let file_row = r + self.row_off;
+    /* render column where a part of a row starts on screen */
+    fn segment_start(&self, sub: u32) -> u32 {
+        if self.cfg.soft_wrap {
+            sub * self.text_width()
+        } else {
+            self.col_off
+        }
+    }
 
-            if file_row < self.rows_num {
-                self.draw_file(file_row);
-            } else if self.rows_num == 0
-                && (r == self.cfg.screen_row / 3 || r == self.cfg.screen_row / 3 + 1) {
-                self.draw_hello(r);
-            } else {
-                self.stdout.write_all(b"~").unwrap();
+    fn cursor_sub_row(&self) -> u32 {
+        if !self.cfg.soft_wrap {
+            return 0;
+        }
+        min(self.rx / self.text_width().max(1), self.wrapped_rows(self.cy) - 1)
+    }
+
+    fn draw_rows(&mut self, lines: &[Option<(u32, u32)>], bracket: Option<(u32, u32)>) {
+        for (r, line) in lines.iter().enumerate() {
+            let r = r as u32;
+            match *line {
+                Some((file_row, sub)) => {
+                    let bracket = bracket.filter(|b| b.1 == file_row).map(|b| self.cx_to_rx(file_row as usize, b.0) as usize);
+                    self.draw_file(file_row, sub, bracket)
+                }
+                None if self.rows_num == 0
+                    && (r == self.cfg.screen_row / 3 || r == self.cfg.screen_row / 3 + 1) => self.draw_hello(r),
+                None => self.stdout.write_all(b"~").unwrap(),
             }
 
             // clean
@@ -296,36 +715,48 @@ impl Editor {
         }
     }
 
-    fn draw_file(&mut self, file_row: u32) {
-        // line number
-        self.stdout.write_all(format!("{:^4}", file_row + 1).as_bytes()).unwrap();
+    /* `bracket`: render column of the partner of the bracket at the cursor, when on this row */
+    fn draw_file(&mut self, file_row: u32, sub: u32, bracket: Option<usize>) {
+        // line number, only on the first part of a wrapped row
+        if self.cfg.line_numbers {
+            let number = if sub == 0 { (file_row + 1).to_string() } else { String::new() };
+            self.stdout.write_all(format!("{:^4}", number).as_bytes()).unwrap();
+        }
         // file row content
         let row = &self.render[file_row as usize];
-        if self.col_off < row.len() as u32 {
-            let start = self.col_off as usize;
-            let end = min(row.len(), (self.col_off + self.cfg.screen_col - 4) as usize);
+        let start = self.segment_start(sub);
+        if start < row.len() as u32 {
+            let end = min(row.len(), (start + self.text_width()) as usize);
+            let start = start as usize;
+            let sel = self.selected_render_range(file_row);
+            let marks = self.cursor_marks(file_row);
+            let marks = (&marks[..], bracket);
 
             // comment
-            if self.in_comment[file_row as usize] {
-                self.stdout.write_all(Highlight::MComment.to_color().as_bytes()).unwrap();
-                self.stdout.write_all(&row[start..end]).unwrap();
-                self.stdout.write_all(Highlight::Normal.to_color().as_bytes()).unwrap();
+            let r = if self.in_comment[file_row as usize] {
+                let hl = vec![Highlight::MComment; row.len()];
+                self.highlight_line(row, &hl, start, end, sel, marks)
             } else {
                 // syntax highlighting
-                let r = self.highlight_line(row, &self.hl[file_row as usize], start, end);
-                self.stdout.write(r.as_bytes()).unwrap();
-            }
+                self.highlight_line(row, &self.hl[file_row as usize], start, end, sel, marks)
+            };
+            self.stdout.write_all(&r).unwrap();
+        }
+        // an extra cursor past the end of the row
+        let len = self.render[file_row as usize].len();
+        if start as usize <= len && len < (start + self.text_width()) as usize && self.cursor_marks(file_row).contains(&len) {
+            self.stdout.write_all(b"\x1b[7m \x1b[27m").unwrap();
         }
     }
 
     fn draw_hello(&mut self, r: u32) {
         let mut welcome = format!("My editor -- version:{}", VERSION);
         if r == self.cfg.screen_row / 3 + 1 {
-            welcome = "HELP: Ctrl-S = save | Ctrl-Q = quit | Ctrl-F = find".to_string();
+            welcome = self.keymap.help_text();
         }
 
-        let welcome = &welcome[..min(welcome.len(), self.cfg.screen_col as usize)];
-        let mut padding = (self.cfg.screen_col.wrapping_sub(welcome.len() as u32)) / 2;
+        let welcome = truncate_chars(&welcome, self.cfg.screen_col as usize);
+        let mut padding = (self.cfg.screen_col - welcome.chars().count() as u32) / 2;
         if padding > 0 {
             self.stdout.write_all(b"~").unwrap();
             padding -= 1;
@@ -340,12 +771,27 @@ impl Editor {
     fn draw_status_bar(&mut self) {
         self.stdout.write_all(b"\x1b[7m").unwrap();
 
-        let status = format!("{:20} - {} lines {}", self.cfg.get_file_name(), self.rows_num, self.get_dirty_status());
-        let line = match self.syntax {
-            None => format!("no ft | {}/{}", self.cy + 1, self.rows_num),
-            Some(syntax) => format!("{} | {}/{}", syntax.file_type, self.cy + 1, self.rows_num),
-        };
-        let spaces: String = repeat(' ').take(self.cfg.screen_col as usize - status.len() - line.len()).collect();
+        let mut name = self.cfg.get_file_name().to_string();
+        if self.files.len() > 1 {
+            name = format!("{} ({} of {})", name, self.file_index + 1, self.files.len());
+        }
+        if self.cfg.read_only {
+            name.push_str(" [RO]");
+        }
+        if self.visual {
+            name.push_str(match self.sel_kind {
+                SelKind::Char => " [VISUAL]",
+                SelKind::Line => " [VISUAL LINE]",
+                SelKind::Block => " [VISUAL BLOCK]",
+            });
+        }
+        if !self.cursors.is_empty() {
+            name.push_str(&format!(" [{} cursors]", self.cursors.len() + 1));
+        }
+        let status = format!("{:20} - {} lines {}", name, self.rows_num, self.get_dirty_status());
+        let file_type = self.syntax.map_or("no ft", |syntax| syntax.file_type);
+        let line = format!("{} | {} | {}/{}", file_type, self.format_status(), self.cy + 1, self.rows_num);
+        let spaces = " ".repeat((self.cfg.screen_col as usize).saturating_sub(status.len() + line.len()));
 
         self.stdout.write_all(status.as_bytes()).unwrap();
         self.stdout.write_all(spaces.as_bytes()).unwrap();
@@ -356,34 +802,133 @@ impl Editor {
 
     fn draw_status_msg(&mut self) {
         self.stdout.write_all(b"\x1b[K").unwrap();
-        if self.status_msg.len() > 0 && get_current_time_secs() - self.status_msg_time < 5 {
+        let timeout = self.cfg.status_msg_timeout;
+        if !self.status_msg.is_empty() && (timeout == 0 || get_current_time_secs() - self.status_msg_time < timeout) {
             self.stdout.write_all(self.status_msg.as_bytes()).unwrap();
         }
     }
 
+    /* show `question` and wait for a key */
+    fn ask(&mut self, question: &str) -> KeyEvent {
+        loop {
+            self.set_status_msg(format_args!("{}", question));
+            self.refresh_screen();
+            if let Event::Key(key) = self.wait_event(true).unwrap_or(Event::Key(KeyEvent::plain(KeyCode::Esc))) {
+                self.set_status_msg(format_args!(""));
+                return key;
+            }
+        }
+    }
+
+    fn ask_yes_no(&mut self, question: &str) -> bool {
+        matches!(self.ask(question).code, KeyCode::Char('y' | 'Y'))
+    }
+
     fn set_status_msg(&mut self, args: Arguments<'_>) {
         self.status_msg = format(args);
         self.status_msg_time = get_current_time_secs();
+        if self.cfg.status_msg_timeout > 0 {
+            self.events.set_timer(Task::StatusMsg, Duration::from_secs(self.cfg.status_msg_timeout + 1));
+        }
     }
 
-    /* file */
-    fn edit_or_open(&mut self) {
-        if self.cfg.file_name != "" {
-            if let Ok(file) = File::open(&self.cfg.file_name) {
-                let reader = BufReader::new(file);
-                self.select_syntax();
-
-                for line in reader.lines() {
-                    let line = line.unwrap().replace("\r", "").replace("\n", "");
-                    self.insert_new_row(self.rows_num as usize, line.as_bytes().to_vec());
+    /* user settings, then everything derived from them */
+    fn load_config(&mut self) -> Result<(), String> {
+        let path = self.cfg.config_file.clone().or_else(|| config::config_dir().map(|dir| dir.join("config.toml")));
+        let loaded = match path {
+            Some(path) => self.cfg.load(&path),
+            None => Ok(()),
+        };
+        self.apply_settings();
+        loaded
+    }
+
+    fn apply_settings(&mut self) {
+        if let Err(e) = self.stdout.set_alt_screen(self.cfg.alt_screen) {
+            self.set_status_msg(format_args!("Can't switch screens: {}", e));
+        }
+        self.events.input.set_esc_timeout(Duration::from_millis(self.cfg.esc_timeout));
+        self.quit_time = self.cfg.quit_times;
+        for i in 0..self.rows_num as usize {
+            self.update_render_and_hl(i);
+        }
+    }
+
+    /* settings for the file being edited: its type's defaults, the
+       `[filetype.*]` part of the config file, then `.editorconfig` */
+    fn apply_file_settings(&mut self) -> Result<(), String> {
+        let mut settings: Vec<(String, Value)> = Vec::new();
+        if let Some(syntax) = self.syntax {
+            settings.extend(syntax.settings.iter().map(|(name, value)| (name.to_string(), value.clone())));
+            settings.extend(self.cfg.settings_for(syntax.file_type));
+        }
+        let mut result = Ok(());
+        self.fixed_format.clear();
+        for (name, value) in settings {
+            self.cfg.set(&name, &value).unwrap();
+            self.fix_format(&name);
+        }
+        if !self.cfg.file_name.is_empty() {
+            let props = editorconfig::properties(Path::new(&self.cfg.file_name));
+            for (name, value) in editorconfig::settings(&props) {
+                match self.cfg.set(name, &value) {
+                    Ok(()) => self.fix_format(name),
+                    Err(e) => result = result.and(Err(format!(".editorconfig: {}", e))),
                 }
+            }
+        }
+        self.apply_settings();
+        result
+    }
 
-                self.update_in_comment();
+    fn fix_format(&mut self, name: &str) {
+        match config::canonical_name(name) {
+            "line_ending" => self.fixed_format.push("line_ending"),
+            "final_newline" => self.fixed_format.push("final_newline"),
+            "bom" => self.fixed_format.push("bom"),
+            _ => {}
+        }
+    }
 
-                self.dirty = false;
-            } else {
-                self.set_status_msg(format_args!("File not exist. Open a empty file. Please use Ctrl-s to Save file!!"))
+    /* user key bindings, kept on top of the defaults */
+    fn load_keymap(&mut self) -> Result<(), String> {
+        if let Some(dir) = config::config_dir() {
+            self.keymap = Keymap::load(&dir.join("keymap"))?;
+        }
+        Ok(())
+    }
+
+    /* the text is to be saved as `name` from now on, with the settings of its type */
+    fn set_file_name(&mut self, name: String) {
+        self.cfg.file_name = name;
+        self.select_syntax();
+        if let Err(e) = self.apply_file_settings() {
+            self.set_status_msg(format_args!("{}", e));
+        }
+        self.re_build_row_highlight();
+    }
+
+    /* file */
+    fn edit_or_open(&mut self) {
+        if !self.cfg.file_name.is_empty() {
+            self.select_syntax();
+            if let Err(e) = self.apply_file_settings() {
+                self.set_status_msg(format_args!("{}", e));
             }
+            let text = match fs::read(&self.cfg.file_name) {
+                Ok(text) => text,
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::NotFound {
+                        self.set_status_msg(format_args!("File not exist. Open a empty file. Please use Ctrl-s to Save file!!"));
+                    } else {
+                        self.set_status_msg(format_args!("Can't open! I/O error: {}", e));
+                    }
+                    Vec::new()
+                }
+            };
+            self.load_text(&text);
+            self.stamp_file(&text);
+            self.check_swap(&text);
         }
     }
 
@@ -395,7 +940,7 @@ impl Editor {
             self.insert_new_row(self.rows_num as usize, Vec::new());
         }
 
-        if let Ok(_) = self.insert_u8_to_row(self.cy as usize, self.cx as usize, c) {
+        if self.insert_u8_to_row(self.cy as usize, self.cx as usize, c).is_ok() {
             self.dirty = true;
             self.cx = self.cx.wrapping_add(1);
             self.update_in_comment();
@@ -407,7 +952,7 @@ impl Editor {
         if self.cx == 0 && self.cy == 0 { return; }
 
         if self.cx > 0 {
-            if let Ok(_) = self.delete_u8_of_row(self.cy as usize, self.cx as usize - 1) {
+            if self.delete_u8_of_row(self.cy as usize, self.cx as usize - 1).is_ok() {
                 self.dirty = true;
                 self.cx = self.cx.saturating_sub(1);
             }
@@ -423,6 +968,12 @@ impl Editor {
     }
 
     fn insert_new_line(&mut self) {
+        // within the leading whitespace there is no indentation to carry over, but for a blank row's
+        if self.cfg.auto_indent && self.cy < self.rows_num && self.cx > 0 && (self.cx as usize
+            > util::indent_len(&self.row[self.cy as usize]) || self.cx as usize == self.row[self.cy as usize].len()) {
+            self.indent_new_line();
+            return;
+        }
         if self.cx == 0 {
             self.insert_new_row(self.cy as usize, Vec::new());
         } else {
@@ -436,26 +987,102 @@ impl Editor {
         self.update_in_comment();
     }
 
-    fn save_file(&mut self) {
-        if self.cfg.file_name == "" {
-            self.cfg.file_name = self.promotion_read(String::from("Save as: {} (ESC to cancel)"), |_, _, _| {});
-            if self.cfg.file_name == "" {
+    /* insert a block of text at the cursor, line breaks included */
+    fn insert_text(&mut self, text: &[u8]) {
+        if text.is_empty() { return; }
+        if self.cy == self.rows_num {
+            self.insert_new_row(self.rows_num as usize, Vec::new());
+        }
+
+        let tail = self.delete_content_to_end(self.cy as usize, self.cx as usize);
+        let mut lines = util::split_lines(text).into_iter();
+        if let Some(first) = lines.next() {
+            self.insert_line_to_row(self.cy as usize, first.to_vec());
+        }
+        for line in lines {
+            self.cy += 1;
+            self.insert_new_row(self.cy as usize, line.to_vec());
+        }
+        self.cx = self.insert_line_to_row(self.cy as usize, tail) as u32;
+
+        self.dirty = true;
+        self.update_in_comment();
+    }
+
+    /* `force` writes even a read-only file */
+    fn save_file(&mut self, force: bool) {
+        if self.cfg.read_only && !force {
+            self.set_status_msg(format_args!("File is read-only (use :w! to write anyway)"));
+            return;
+        }
+        if self.cfg.file_name.is_empty() {
+            let name = self.promotion_read(String::from("Save as: {} (ESC to cancel)"), |_, _, _| {});
+            if name.is_empty() {
                 self.set_status_msg(format_args!("Save aborted"));
                 return;
             }
-            self.select_syntax();
-            self.re_build_row_highlight();
-        } else {}
-        let mut file = File::create(&self.cfg.file_name).unwrap();
-        let mut bytes: u32 = 0;
-        for i in 0..self.rows_num {
-            file.write_all(&self.row[i as usize]).unwrap();
-            file.write_all(b"\n").unwrap();
-            bytes += self.row[i as usize].len() as u32 + 1;
+            self.set_file_name(name);
+        }
+
+        if !force && self.changed_on_disk() && !self.ask_yes_no("The file changed on disk since it was read. Write anyway? (y/n)") {
+            self.set_status_msg(format_args!("Not written"));
+            return;
+        }
+        if self.cfg.trim_trailing_whitespace {
+            self.trim_trailing_whitespace();
         }
-        file.flush().unwrap();
+
+        if let Err(e) = self.write_backup() {
+            if !force {
+                self.set_status_msg(format_args!("{}", e));
+                return;
+            }
+        }
+
+        match self.write_file() {
+            Ok(len) => self.set_status_msg(format_args!("{} bytes written to disk", len)),
+            Err(e) => self.set_status_msg(format_args!("Can't save! I/O error: {}", e)),
+        }
+    }
+
+    /* the buffer to its file as it is, returning how many bytes that took */
+    fn write_file(&mut self) -> io::Result<usize> {
+        let content = self.file_content();
+        fileio::write_atomic(Path::new(&self.cfg.file_name), &content)?;
+        self.stamp_file(&content);
         self.dirty = false;
-        self.set_status_msg(format_args!("{} bytes written to disk", bytes));
+        self.mixed_endings = false;
+        self.remove_swap();
+        Ok(content.len())
+    }
+
+    /* the buffer as it is written to disk */
+    fn file_content(&self) -> Vec<u8> {
+        let mut content = Vec::new();
+        if self.cfg.bom {
+            content.extend_from_slice(fileio::BOM);
+        }
+        for i in 0..self.rows_num as usize {
+            content.extend_from_slice(&self.row[i]);
+            if i + 1 < self.rows_num as usize || self.cfg.final_newline {
+                content.extend_from_slice(self.cfg.line_ending.as_bytes());
+            }
+        }
+        content
+    }
+
+    fn trim_trailing_whitespace(&mut self) {
+        for i in 0..self.rows_num as usize {
+            let len = self.row[i].iter().rposition(|c| !matches!(c, b' ' | b'\t')).map_or(0, |p| p + 1);
+            if len < self.row[i].len() {
+                self.row[i].truncate(len);
+                self.update_render_and_hl(i);
+            }
+        }
+        if self.cy < self.rows_num {
+            self.cx = min(self.cx, self.row[self.cy as usize].len() as u32);
+        }
+        self.clamp_cursors();
     }
 
     fn find_world(&mut self) {
@@ -465,7 +1092,10 @@ impl Editor {
         let save_row_off = self.row_off;
 
         let world = self.promotion_read(String::from("Search: {} (Use ESC/Arrows/Enter)"), Editor::find_world_callback);
-        if world == "" {
+        if !world.is_empty() {
+            self.last_search = world.clone();
+        }
+        if world.is_empty() {
             self.cx = save_cx;
             self.cy = save_cy;
             self.col_off = save_col_off;
@@ -473,15 +1103,15 @@ impl Editor {
         }
     }
 
-    fn find_world_callback(&mut self, keys: &Keys, world: &str) {
+    fn find_world_callback(&mut self, key: &KeyEvent, world: &str) {
         if self.last_match != -1 {
             Highlight::copy_highlight(&mut self.hl[self.last_match as usize], &self.saved_match_hl);
         }
 
-        match keys {
-            Keys::ARROW_UP => self.find_direction = -1,
-            Keys::ARROW_DOWN => self.find_direction = 1,
-            Keys::ENTER | Keys::ESC => {
+        match key.code {
+            KeyCode::Up => self.find_direction = -1,
+            KeyCode::Down => self.find_direction = 1,
+            KeyCode::Enter | KeyCode::Esc => {
                 self.last_match = -1;
                 self.find_direction = 1;
                 return;
@@ -517,13 +1147,13 @@ impl Editor {
     fn get_render_vec(&self, line: &Vec<u8>) -> Vec<u8> {
         let mut render_vec = Vec::new();
         for c in line {
-            if *c == '\t' as u8 {
-                render_vec.push(' ' as u8);
-                while render_vec.len() % TABLE_STOP as usize != 0 {
-                    render_vec.push(' ' as u8);
+            if *c == b'\t' {
+                render_vec.push(b' ');
+                while render_vec.len() % self.cfg.tab_width as usize != 0 {
+                    render_vec.push(b' ');
                 }
             } else {
-                render_vec.push(c.clone());
+                render_vec.push(*c);
             }
         }
         render_vec
@@ -532,14 +1162,44 @@ impl Editor {
     fn row_cx_to_rx(&mut self) {
         self.rx = 0;
         if self.cy < self.rows_num {
-            for i in 0..self.cx {
-                if self.row[self.cy as usize][i as usize] == '\t' as u8 {
-                    self.rx += TABLE_STOP as u32 - (self.rx % TABLE_STOP as u32)
-                } else {
-                    self.rx += 1;
-                }
+            self.rx = self.cx_to_rx(self.cy as usize, self.cx);
+        }
+    }
+
+    fn cx_to_rx(&self, y: usize, cx: u32) -> u32 {
+        let mut rx = 0;
+        for c in &self.row[y][..min(cx as usize, self.row[y].len())] {
+            if *c == b'\t' {
+                rx += self.cfg.tab_width - (rx % self.cfg.tab_width)
+            } else {
+                rx += 1;
             }
         }
+        rx
+    }
+
+    fn rx_to_cx(&self, y: usize, rx: u32) -> u32 {
+        let mut cur = 0;
+        for (cx, c) in self.row[y].iter().enumerate() {
+            if *c == b'\t' {
+                cur += self.cfg.tab_width - (cur % self.cfg.tab_width)
+            } else {
+                cur += 1;
+            }
+            if cur > rx {
+                return cx as u32;
+            }
+        }
+        self.row[y].len() as u32
+    }
+
+    fn gutter_width(&self) -> u32 {
+        if self.cfg.line_numbers { GUTTER } else { 0 }
+    }
+
+    /* screen columns left for the text next to the line numbers */
+    fn text_width(&self) -> u32 {
+        self.cfg.screen_col.saturating_sub(self.gutter_width())
     }
 
     fn get_dirty_status(&self) -> &'static str {
@@ -551,44 +1211,59 @@ impl Editor {
     }
 
     fn select_syntax(&mut self) {
-        if self.cfg.file_name == "" { return; }
+        if self.cfg.file_name.is_empty() { return; }
 
-        let ext = util::get_file_type(&self.cfg.file_name);
-        for s in syntax::HLDB.iter() {
-            for t in s.file_math.iter() {
-                if *t == ext {
-                    self.syntax = Some(s);
-                    break;
-                }
-            }
-        }
+        self.syntax = syntax::HLDB.iter().find(|s| s.matches(&self.cfg.file_name));
     }
 
-    fn highlight_line(&self, line: &Vec<u8>, hl: &Vec<Highlight>, start: usize, end: usize) -> String {
-        let mut hl_str = String::new();
+    /* `sel` is the selected range of render columns, drawn reversed over the syntax colors */
+    /* `marks`: the extra cursors, and the bracket matching the one at the cursor */
+    fn highlight_line(&self, line: &[u8], hl: &[Highlight], start: usize, end: usize, sel: (usize, usize),
+                      marks: (&[usize], Option<usize>)) -> Vec<u8> {
+        let (marks, bracket) = marks;
+        let mut hl_str = Vec::new();
+        let mut color = None;
+        let mut selected = false;
 
         for i in start..end {
+            // the selection and the extra cursors show in reverse
+            if ((sel.0..sel.1).contains(&i) || marks.contains(&i)) != selected {
+                selected = !selected;
+                hl_str.extend_from_slice(if selected { b"\x1b[7m" } else { b"\x1b[27m" });
+            }
             if line[i].is_ascii_control() {
-                hl_str.push_str("\x1b[7m");
+                hl_str.extend_from_slice(if selected { b"\x1b[27m" } else { b"\x1b[7m" });
                 if line[i] <= 26 {
-                    hl_str.push('@');
+                    hl_str.push(b'@');
                 } else {
-                    hl_str.push('?');
+                    hl_str.push(b'?');
                 }
-                hl_str.push_str("\x1b[m");
+                hl_str.extend_from_slice(if selected { b"\x1b[7m" } else { b"\x1b[27m" });
                 continue;
             }
-            if i == start || hl[i] != hl[i - 1] {
-                hl_str.push_str(hl[i].to_color());
+            if color != Some(&hl[i]) {
+                color = Some(&hl[i]);
+                hl_str.extend_from_slice(hl[i].to_color(self.cfg.theme).as_bytes());
+            }
+            if bracket == Some(i) {
+                // bold and underlined, then the color again as the mono theme uses both
+                hl_str.extend_from_slice(b"\x1b[1;4m");
+                hl_str.push(line[i]);
+                hl_str.extend_from_slice(b"\x1b[22;24m");
+                color = None;
+                continue;
             }
-            hl_str.push(line[i] as char);
+            hl_str.push(line[i]);
         }
 
-        hl_str.push_str(Highlight::Normal.to_color());
+        if selected {
+            hl_str.extend_from_slice(b"\x1b[27m");
+        }
+        hl_str.extend_from_slice(Highlight::Normal.to_color(self.cfg.theme).as_bytes());
         hl_str
     }
 
-    fn build_row_highlight(&self, line: &Vec<u8>) -> Vec<Highlight> {
+    fn build_row_highlight(&self, line: &[u8]) -> Vec<Highlight> {
         match self.syntax {
             None => {
                 let mut r = Vec::new();
@@ -682,3 +1357,132 @@ impl Editor {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::clipboard::{ClipKind, ClipboardMode};
+    use crate::config::TEST_STATE_DIR;
+    use crate::util::TempDir;
+    use super::*;
+
+    /* an editor with `text` that draws nowhere and keeps its state in `dir` */
+    pub(super) fn editor(dir: &TempDir, text: &[u8]) -> Editor {
+        TEST_STATE_DIR.with(|state| *state.borrow_mut() = Some(dir.path.join("state")));
+        let mut cfg = EditorCfg::new();
+        cfg.clipboard = ClipboardMode::Internal;
+        // whether the screen is switched is kept for the whole process, leave it be
+        cfg.alt_screen = false;
+        let mut e = Editor::with_terminal(cfg, Terminal::detached());
+        e.load_text(text);
+        e
+    }
+
+    #[test]
+    fn test_selection_after_trim() {
+        let dir = TempDir::new();
+        let mut e = editor(&dir, b"abc  \ndef\n");
+        e.cfg.trim_trailing_whitespace = true;
+        e.cfg.file_name = dir.path.join("trim.txt").to_string_lossy().into_owned();
+        // anchored in the whitespace that goes
+        e.execute(Command::LineEnd);
+        e.start_selection(SelKind::Char);
+        e.execute(Command::LineStart);
+        e.execute(Command::Save);
+        assert_eq!(b"abc\ndef\n", &fs::read(&e.cfg.file_name).unwrap()[..]);
+        e.execute(Command::Copy);
+        assert_eq!(Some(&b"abc"[..]), e.registers.get('0').map(|clip| &clip.text[..]));
+    }
+
+    /* a block from (cx, cy), moved over by `down` rows and `right` columns */
+    fn select_block(e: &mut Editor, (cx, cy): (u32, u32), down: u32, right: u32) {
+        (e.cx, e.cy) = (cx, cy);
+        e.execute(Command::VisualBlock);
+        for _ in 0..down {
+            e.execute(Command::MoveDown);
+        }
+        for _ in 0..right {
+            e.execute(Command::MoveRight);
+        }
+    }
+
+    fn type_text(e: &mut Editor, text: &str) {
+        for c in text.chars() {
+            e.process_event(Event::Key(KeyEvent::plain(KeyCode::Char(c))));
+        }
+    }
+
+    #[test]
+    fn test_block_yank_delete_paste() {
+        let dir = TempDir::new();
+        let mut e = editor(&dir, b"abcdef\nab\nabcdef\n");
+        select_block(&mut e, (1, 0), 2, 2);
+        e.execute(Command::Copy);
+        let clip = e.registers.get('0').unwrap();
+        assert_eq!((&b"bc\nb\nbc"[..], ClipKind::Block), (&clip.text[..], clip.kind));
+        assert!(e.sel_anchor.is_none());
+
+        select_block(&mut e, (1, 0), 2, 2);
+        e.execute(Command::Cut);
+        assert_eq!(vec![b"adef".to_vec(), b"a".to_vec(), b"adef".to_vec()], e.row);
+        assert_eq!((1, 0), (e.cx, e.cy));
+
+        // back where it came from, then again past the end of the short row
+        e.execute(Command::Paste);
+        assert_eq!(vec![b"abcdef".to_vec(), b"ab".to_vec(), b"abcdef".to_vec()], e.row);
+        e.execute(Command::LineEnd);
+        e.execute(Command::Paste);
+        assert_eq!(vec![b"abcdefbc".to_vec(), b"ab    b".to_vec(), b"abcdefbc".to_vec()], e.row);
+        assert_eq!((6, 0), (e.cx, e.cy));
+    }
+
+    #[test]
+    fn test_block_insert_append() {
+        let dir = TempDir::new();
+        let mut e = editor(&dir, b"abcd\na\nabcd\n");
+        select_block(&mut e, (2, 0), 2, 1);
+        e.execute(Command::BlockInsert);
+        type_text(&mut e, "X");
+        // the row ending left of the block gets nothing
+        assert_eq!(vec![b"abXcd".to_vec(), b"a".to_vec(), b"abXcd".to_vec()], e.row);
+
+        let mut e = editor(&dir, b"abcd\na\nabcd\n");
+        select_block(&mut e, (2, 0), 2, 1);
+        e.execute(Command::BlockAppend);
+        type_text(&mut e, "Y");
+        // and is padded to the block's right edge for appending
+        assert_eq!(vec![b"abcYd".to_vec(), b"a  Y".to_vec(), b"abcYd".to_vec()], e.row);
+        assert!(e.sel_anchor.is_none());
+    }
+
+    #[test]
+    fn test_indent_at_cursors() {
+        let dir = TempDir::new();
+        let mut e = editor(&dir, b"a b\nc\n");
+        e.cfg.expand_tabs = true;
+        e.execute(Command::MoveRight);
+        e.execute(Command::AddCursorBelow);
+        e.cursors.push((3, 0));
+        e.execute(Command::Indent);
+        assert_eq!((b"    a b".to_vec(), b"    c".to_vec()), (e.row[0].clone(), e.row[1].clone()));
+        assert_eq!(vec![(5, 0), (7, 0)], e.cursors);
+        assert_eq!((5, 1), (e.cx, e.cy));
+    }
+
+    #[test]
+    fn test_tab_to_tab_stop() {
+        let dir = TempDir::new();
+        for (tab_width, indent_width, spaces) in [(8, 4, 7), (4, 8, 3), (4, 0, 3)] {
+            let mut e = editor(&dir, b"ab\n");
+            e.cfg.expand_tabs = true;
+            e.cfg.tab_width = tab_width;
+            e.cfg.indent_width = indent_width;
+            e.execute(Command::MoveRight);
+            e.execute(Command::InsertTab);
+            assert_eq!([b"a".to_vec(), vec![b' '; spaces], b"b".to_vec()].concat(), e.row[0]);
+
+            /* indenting a line still goes by the indent width */
+            e.execute(Command::Indent);
+            assert_eq!(e.cfg.indent() as usize + 1 + spaces + 1, e.row[0].len());
+        }
+    }
+}
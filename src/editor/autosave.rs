@@ -0,0 +1,85 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::fileio;
+use super::{Editor, Task};
+
+/* saving without being asked, and keeping the file as it was */
+impl Editor {
+    /* after each input: autosave once there has been none for a while */
+    pub(super) fn schedule_autosave(&mut self) {
+        if self.cfg.autosave > 0 && self.dirty {
+            self.events.set_timer(Task::Autosave, Duration::from_secs(self.cfg.autosave));
+        } else {
+            self.events.cancel_timer(Task::Autosave);
+        }
+    }
+
+    /* write the text as it is, without trimming it or asking anything */
+    pub(super) fn autosave(&mut self) {
+        if !self.dirty || self.cfg.file_name.is_empty() || self.cfg.read_only {
+            return;
+        }
+        if self.changed_on_disk() {
+            self.set_status_msg(format_args!("Not autosaved: the file changed on disk since it was read"));
+            return;
+        }
+        if let Err(e) = self.write_backup() {
+            self.set_status_msg(format_args!("Not autosaved: {}", e));
+            return;
+        }
+        match self.write_file() {
+            Ok(len) => self.set_status_msg(format_args!("{} bytes written to disk", len)),
+            Err(e) => self.set_status_msg(format_args!("Can't autosave! I/O error: {}", e)),
+        }
+    }
+
+    /* before the first save of the file as read: copy it to `file~` or the backup directory */
+    pub(super) fn write_backup(&mut self) -> Result<(), String> {
+        if self.backed_up || !self.cfg.backup && self.cfg.backup_dir.is_empty() {
+            return Ok(());
+        }
+        let dir = match self.cfg.backup_dir.as_str() {
+            "" => None,
+            dir => Some(expand_home(dir)),
+        };
+        fileio::backup(Path::new(&self.cfg.file_name), dir.as_deref())
+            .map_err(|e| format!("Can't write backup file (add ! to override): {}", e))?;
+        self.backed_up = true;
+        Ok(())
+    }
+}
+
+fn expand_home(dir: &str) -> PathBuf {
+    match (dir.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(dir),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use crate::util::TempDir;
+    use super::super::test::editor;
+
+    #[test]
+    fn test_autosave() {
+        let dir = TempDir::new();
+        let mut e = editor(&dir, b"abc  \n");
+        e.cfg.file_name = dir.path.join("auto.txt").to_string_lossy().into_owned();
+        e.cfg.trim_trailing_whitespace = true;
+        e.insert_char(b'x');
+        e.autosave();
+        assert!(!e.dirty);
+        assert_eq!(b"xabc  \n".to_vec(), fs::read(&e.cfg.file_name).unwrap());
+
+        /* someone else wrote the file meanwhile: leave it to them */
+        fs::write(&e.cfg.file_name, b"theirs\n").unwrap();
+        e.insert_char(b'y');
+        e.autosave();
+        assert!(e.dirty);
+        assert!(e.status_msg.starts_with("Not autosaved"));
+        assert_eq!(b"theirs\n".to_vec(), fs::read(&e.cfg.file_name).unwrap());
+    }
+}
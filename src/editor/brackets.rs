@@ -0,0 +1,154 @@
+use super::Editor;
+
+const PAIRS: &[(u8, u8)] = &[(b'(', b')'), (b'[', b']'), (b'{', b'}')];
+/* rows looked through for the partner shown while drawing or an opener to
+   indent by, jumping looks through all */
+const SHOW_MATCH_ROWS: u32 = 1000;
+
+/* matching brackets */
+impl Editor {
+    /* the partner of the bracket at the cursor or just before it, to show on screen */
+    pub(super) fn shown_match(&self) -> Option<(u32, u32)> {
+        let bracket = self.bracket_at_cursor()?;
+        self.find_partner(bracket, SHOW_MATCH_ROWS)
+    }
+
+    /* `%`: to the partner of the bracket at the cursor, or of the first one after it on the row */
+    pub(super) fn jump_to_match(&mut self) {
+        let bracket = self.bracket_at_cursor().or_else(|| {
+            let (y, row) = (self.cy as usize, self.row.get(self.cy as usize)?);
+            (self.cx as usize..row.len()).find(|&x| is_bracket(row[x]) && self.is_code(y, x)).map(|x| (x as u32, self.cy))
+        });
+        match bracket.and_then(|b| self.find_partner(b, self.rows_num)) {
+            Some((x, y)) => (self.cx, self.cy) = (x, y),
+            None => self.set_status_msg(format_args!("No matching bracket")),
+        }
+    }
+
+    /* the opening bracket a `close` typed at the cursor would match */
+    pub(super) fn opener_for(&self, close: u8) -> Option<(u32, u32)> {
+        self.scan_for_partner(close, ((self.cx as usize).checked_sub(1), self.cy), 1, SHOW_MATCH_ROWS)
+    }
+
+    fn bracket_at_cursor(&self) -> Option<(u32, u32)> {
+        let (y, row) = (self.cy as usize, self.row.get(self.cy as usize)?);
+        let x = self.cx as usize;
+        [Some(x), x.checked_sub(1)].into_iter().flatten()
+            .find(|&x| row.get(x).is_some_and(|c| is_bracket(*c)) && self.is_code(y, x))
+            .map(|x| (x as u32, self.cy))
+    }
+
+    /* the bracket closing or opening the one at `pos`, no more than `rows` rows away */
+    fn find_partner(&self, pos: (u32, u32), rows: u32) -> Option<(u32, u32)> {
+        let c = self.row[pos.1 as usize][pos.0 as usize];
+        self.scan_for_partner(c, (Some(pos.0 as usize), pos.1), 0, rows)
+    }
+
+    /* from `from` on, away from bracket `c` with `depth` of them already
+       passed, the bracket that pairs with it */
+    fn scan_for_partner(&self, c: u8, from: (Option<usize>, u32), mut depth: i32, rows: u32) -> Option<(u32, u32)> {
+        let &(open, close) = PAIRS.iter().find(|(o, e)| *o == c || *e == c)?;
+        let forward = c == open;
+        let (mut x, mut y) = from;
+        let last = if forward {
+            y.saturating_add(rows).min(self.rows_num - 1)
+        } else {
+            y.saturating_sub(rows)
+        };
+
+        loop {
+            let row = &self.row[y as usize];
+            // render columns for telling code from strings and comments, worked out once a row
+            let mut rxs = None;
+            while let Some(i) = x.filter(|i| *i < row.len()) {
+                if (row[i] == open || row[i] == close)
+                    && self.is_code_at_rx(y as usize, rxs.get_or_insert_with(|| render_cols(row, self.cfg.tab_width))[i]) {
+                    depth += if row[i] == c { 1 } else { -1 };
+                    if depth == 0 {
+                        return Some((i as u32, y));
+                    }
+                }
+                x = if forward { Some(i + 1) } else { i.checked_sub(1) };
+            }
+            if y == last {
+                return None;
+            }
+            y = if forward { y + 1 } else { y - 1 };
+            x = if forward { Some(0) } else { self.row[y as usize].len().checked_sub(1) };
+        }
+    }
+}
+
+fn is_bracket(c: u8) -> bool {
+    PAIRS.iter().any(|(open, close)| c == *open || c == *close)
+}
+
+/* the render column of each character of `row` */
+fn render_cols(row: &[u8], tab_width: u32) -> Vec<u32> {
+    let mut rx = 0;
+    row.iter().map(|c| {
+        let col = rx;
+        rx += if *c == b'\t' { tab_width - rx % tab_width } else { 1 };
+        col
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::util::TempDir;
+    use super::super::Editor;
+    use super::super::test::editor;
+    use super::SHOW_MATCH_ROWS;
+
+    fn c_editor(dir: &TempDir, text: &[u8]) -> Editor {
+        let mut e = editor(dir, text);
+        e.cfg.file_name = "main.c".to_string();
+        e.select_syntax();
+        e.update_in_comment();
+        e.re_build_row_highlight();
+        e
+    }
+
+    #[test]
+    fn test_match_across_lines() {
+        let dir = TempDir::new();
+        let mut e = c_editor(&dir, b"int f() {\n\tif (a) {\n\t\tg(b[0]);\n\t}\n}\n");
+        e.cx = 8;
+        assert_eq!(Some((0, 4)), e.shown_match());
+        (e.cx, e.cy) = (1, 3);
+        assert_eq!(Some((8, 1)), e.shown_match());
+        (e.cx, e.cy) = (3, 2);
+        assert_eq!(Some((8, 2)), e.shown_match());
+        e.jump_to_match();
+        assert_eq!((8, 2), (e.cx, e.cy));
+    }
+
+    #[test]
+    fn test_match_skips_strings_and_comments() {
+        let dir = TempDir::new();
+        let mut e = c_editor(&dir, b"f(\"(\", ')', // )\n/* (\n ) */\n\ta)\n");
+        e.cx = 1;
+        assert_eq!(Some((2, 3)), e.shown_match());
+        (e.cx, e.cy) = (2, 3);
+        assert_eq!(Some((1, 0)), e.shown_match());
+
+        /* a bracket in a string has no partner to show */
+        e.cy = 0;
+        e.cx = 3;
+        assert_eq!(None, e.shown_match());
+    }
+
+    #[test]
+    fn test_match_scan_limit() {
+        let dir = TempDir::new();
+        let text = ["{\n".to_string(), "x;\n".repeat(SHOW_MATCH_ROWS as usize), "}\n".to_string()].concat();
+        let mut e = c_editor(&dir, text.as_bytes());
+        assert_eq!(None, e.shown_match());
+        (e.cx, e.cy) = (0, SHOW_MATCH_ROWS + 1);
+        assert_eq!(None, e.shown_match());
+
+        /* jumping looks through the whole file */
+        e.jump_to_match();
+        assert_eq!((0, 0), (e.cx, e.cy));
+    }
+}
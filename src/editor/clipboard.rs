@@ -0,0 +1,188 @@
+use std::io::Write;
+use std::iter;
+use crate::clipboard::{self, Clip, ClipKind, ClipboardMode, Tool};
+use crate::key::{KeyCode, Modifiers};
+use crate::registers::{self, Registers};
+use super::{Editor, SelKind};
+
+/* cut, copy and paste, through the registers */
+impl Editor {
+    pub(super) fn copy(&mut self) {
+        let Some(clip) = self.clip_selection() else { return };
+        self.set_clipboard(clip, false);
+        self.clear_selection();
+    }
+
+    pub(super) fn cut(&mut self) {
+        let Some(clip) = self.clip_selection() else { return };
+        self.set_clipboard(clip, true);
+        if self.delete_selection() {
+            return;
+        }
+        // no selection, the cursor's line goes
+        if self.rows_num > 1 {
+            self.delete_row(self.cy as usize);
+            self.cy = self.cy.min(self.rows_num - 1);
+        } else {
+            self.delete_content_to_end(0, 0);
+        }
+        self.cx = 0;
+        self.dirty = true;
+        self.update_in_comment();
+    }
+
+    pub(super) fn paste(&mut self) {
+        let Some(clip) = self.get_clipboard() else {
+            self.set_status_msg(format_args!("Nothing to paste"));
+            return;
+        };
+        self.delete_selection();
+        match clip.kind {
+            ClipKind::Chars => return self.insert_text(&clip.text),
+            ClipKind::Block => return self.paste_block(&clip.text),
+            ClipKind::Lines => {}
+        }
+        // whole lines go above the cursor's line, the cursor to the first of them
+        let cy = self.cy as usize;
+        for (i, line) in clip.text.split(|c| *c == b'\n').enumerate() {
+            self.insert_new_row(cy + i, line.to_vec());
+        }
+        self.cx = 0;
+        self.dirty = true;
+        self.update_in_comment();
+    }
+
+    /* Backspace and Delete on a selection, keeping the text in the delete history */
+    pub(super) fn delete_selected(&mut self) -> bool {
+        let register = self.register.take();
+        if self.selection().is_none() {
+            return self.delete_selection();
+        }
+        if register != Some('_') {
+            if let Some(clip) = self.clip_selection() {
+                self.registers.store_deleted(clip);
+                self.save_registers();
+            }
+        }
+        self.delete_selection()
+    }
+
+    /* the register the next cut, copy, paste or delete uses */
+    pub(super) fn select_register(&mut self) {
+        let key = self.ask("Register: ");
+        match key.code {
+            KeyCode::Esc => {}
+            KeyCode::Char(c) if Registers::is_name(c) && !key.mods.intersects(Modifiers::CTRL | Modifiers::ALT) => {
+                self.register = Some(c);
+                self.set_status_msg(format_args!("\"{}", c));
+            }
+            _ => self.set_status_msg(format_args!("Not a register")),
+        }
+    }
+
+    /* `registers`: what each one holds, as far as the status bar goes */
+    pub(super) fn list_registers(&mut self) {
+        let list = self.registers.list().iter().map(|(name, clip)| {
+            let text = String::from_utf8_lossy(&clip.text);
+            let text: String = text.chars().take(20).map(|c| if c == '\n' { '|' } else { c }).collect();
+            format!("\"{} {}", name, text)
+        }).collect::<Vec<_>>().join("  ");
+        if list.is_empty() {
+            self.set_status_msg(format_args!("The registers are empty"));
+        } else {
+            self.set_status_msg(format_args!("{}", list));
+        }
+    }
+
+    /* the registers left by the last session */
+    pub(super) fn load_registers(&mut self) {
+        let Some(path) = registers::registers_path() else { return };
+        match Registers::load(&path) {
+            Ok(registers) => self.registers = registers,
+            Err(e) => self.set_status_msg(format_args!("Can't read {}: {}", path.display(), e)),
+        }
+    }
+
+    fn save_registers(&mut self) {
+        let Some(path) = registers::registers_path() else { return };
+        if let Err(e) = self.registers.save(&path) {
+            self.set_status_msg(format_args!("Can't write {}: {}", path.display(), e));
+        }
+    }
+
+    /* a block goes in at the cursor's column on its row and those below, padding short rows */
+    fn paste_block(&mut self, text: &[u8]) {
+        self.row_cx_to_rx();
+        let (col, top) = (self.rx, self.cy as usize);
+        for (i, piece) in text.split(|c| *c == b'\n').enumerate() {
+            let y = top + i;
+            if y >= self.rows_num as usize {
+                self.insert_new_row(y, Vec::new());
+            }
+            let len = self.render[y].len() as u32;
+            if len < col {
+                self.row[y].extend(iter::repeat_n(b' ', (col - len) as usize));
+            }
+            let x = self.rx_to_cx(y, col) as usize;
+            self.row[y].splice(x..x, piece.iter().copied());
+            self.update_render_and_hl(y);
+        }
+        self.cx = self.rx_to_cx(top, col);
+        self.dirty = true;
+        self.update_in_comment();
+    }
+
+    /* the selected text, or the cursor's line without a selection */
+    fn clip_selection(&self) -> Option<Clip> {
+        match self.selection() {
+            Some(_) if self.sel_kind == SelKind::Line => {
+                let (top, bottom) = self.selected_rows()?;
+                Some(Clip { text: self.row[top as usize..=bottom as usize].join(&b'\n'), kind: ClipKind::Lines })
+            }
+            Some(_) if self.sel_kind == SelKind::Block => Some(Clip { text: self.block_text()?, kind: ClipKind::Block }),
+            Some((start, end)) => Some(Clip { text: self.text_between(start, end), kind: ClipKind::Chars }),
+            None if self.cy < self.rows_num => Some(Clip { text: self.row[self.cy as usize].clone(), kind: ClipKind::Lines }),
+            None => None,
+        }
+    }
+
+    /* only the unnamed register is shared with the system clipboard */
+    fn set_clipboard(&mut self, clip: Clip, cut: bool) {
+        let register = self.register.take().unwrap_or('"');
+        if register == '_' {
+            return;
+        }
+        if register == '"' && self.cfg.clipboard != ClipboardMode::Internal {
+            let text = clip.to_system();
+            let _ = self.stdout.write_all(&clipboard::osc52(&text));
+            let _ = self.stdout.flush();
+            if let (ClipboardMode::Auto, Some(tool)) = (self.cfg.clipboard, Tool::find()) {
+                if let Err(e) = tool.copy(&text) {
+                    self.set_status_msg(format_args!("Can't copy to the system clipboard: {}", e));
+                }
+            }
+        }
+        self.registers.store(register, clip, cut);
+        self.save_registers();
+    }
+
+    /* what the clipboard program has, else what was last cut or copied here */
+    fn get_clipboard(&mut self) -> Option<Clip> {
+        match self.register.take() {
+            Some('_') => return None,
+            Some(register) if register != '"' => return self.registers.get(register).cloned(),
+            _ => {}
+        }
+        let unnamed = self.registers.get('"').cloned();
+        if let (ClipboardMode::Auto, Some(tool)) = (self.cfg.clipboard, Tool::find()) {
+            match tool.paste() {
+                // our own text back, still knowing whether it was whole lines or a block
+                Ok(text) if unnamed.as_ref().is_some_and(|clip| clip.to_system() == text) => {}
+                Ok(text) if !text.is_empty() => return Some(Clip { text, kind: ClipKind::Chars }),
+                Ok(_) => {}
+                Err(e) => self.set_status_msg(format_args!("Can't paste from the system clipboard: {}", e)),
+            }
+        }
+        unnamed
+    }
+}
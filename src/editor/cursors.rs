@@ -0,0 +1,170 @@
+use std::mem;
+use memchr::memmem;
+use crate::util;
+use super::Editor;
+
+/* multiple cursors: the main one at (cx, cy), the others in `cursors` */
+impl Editor {
+    /* run the edit `f` at every cursor, from the top of the file down */
+    pub(super) fn edit_at_cursors(&mut self, mut f: impl FnMut(&mut Self)) {
+        if self.cursors.is_empty() {
+            f(self);
+            return;
+        }
+        let main = (self.cx, self.cy);
+        let mut all = mem::take(&mut self.cursors);
+        all.push(main);
+        all.sort_by_key(|&(x, y)| (y, x));
+        all.dedup();
+        let main = all.iter().position(|c| *c == main).unwrap();
+
+        for i in 0..all.len() {
+            (self.cx, self.cy) = all[i];
+            let rows = self.rows_num;
+            // the cursors further on keep their distance to the end of their row
+            let tails: Vec<u32> = all[i + 1..].iter().map(|&(x, y)| self.row_len(y).saturating_sub(x)).collect();
+            f(self);
+            all[i] = (self.cx, self.cy);
+            for (p, tail) in all[i + 1..].iter_mut().zip(tails) {
+                p.1 = (p.1 as i64 + self.rows_num as i64 - rows as i64) as u32;
+                if p.1 == self.cy {
+                    p.0 = self.row_len(p.1).saturating_sub(tail);
+                }
+            }
+        }
+        (self.cx, self.cy) = all.remove(main);
+        self.cursors = all;
+        self.tidy_cursors();
+    }
+
+    /* move every cursor the way `f` moves the main one */
+    pub(super) fn move_cursors(&mut self, mut f: impl FnMut(&mut Self)) {
+        let main = (self.cx, self.cy);
+        let mut cursors = mem::take(&mut self.cursors);
+        for cursor in &mut cursors {
+            (self.cx, self.cy) = *cursor;
+            f(self);
+            *cursor = (self.cx, self.cy);
+        }
+        (self.cx, self.cy) = main;
+        f(self);
+        self.cursors = cursors;
+        self.tidy_cursors();
+    }
+
+    /* a cursor on the next occurrence of the word under the cursor, the main cursor going there */
+    pub(super) fn add_cursor_next_word(&mut self) {
+        let Some((start, end)) = self.word_at_cursor() else {
+            self.set_status_msg(format_args!("No word under the cursor"));
+            return;
+        };
+        let word = self.row[self.cy as usize][start..end].to_vec();
+        let offset = self.cx - start as u32;
+        let (rows, cy) = (self.rows_num as usize, self.cy as usize);
+        // on from the end of this word, around the end of the file and back to it
+        let next = (0..=rows).find_map(|i| {
+            let y = (cy + i) % rows;
+            find_words(&self.row[y], &word)
+                .filter(|&x| !(i == 0 && x < end || i == rows && x >= start))
+                .map(|x| (x as u32 + offset, y as u32))
+                .find(|pos| !self.cursors.contains(pos))
+        });
+        if let Some(pos) = next {
+            self.cursors.push((self.cx, self.cy));
+            (self.cx, self.cy) = pos;
+            self.tidy_cursors();
+            return;
+        }
+        self.set_status_msg(format_args!("No other occurrence of {}", String::from_utf8_lossy(&word)));
+    }
+
+    /* a cursor on the row above or below in the same column, the main cursor going there */
+    pub(super) fn add_cursor_vertical(&mut self, down: bool) {
+        let y = match down {
+            true if self.cy + 1 < self.rows_num => self.cy + 1,
+            false if self.cy > 0 && self.rows_num > 0 => self.cy - 1,
+            _ => return,
+        };
+        self.row_cx_to_rx();
+        self.cursors.push((self.cx, self.cy));
+        self.cx = self.rx_to_cx(y as usize, self.rx);
+        self.cy = y;
+        self.tidy_cursors();
+    }
+
+    /* a cursor at every match of the last search, the main one at the first from it on */
+    pub(super) fn add_cursors_at_matches(&mut self) {
+        if self.last_search.is_empty() {
+            self.set_status_msg(format_args!("No previous search"));
+            return;
+        }
+        let mut found: Vec<(u32, u32)> = Vec::new();
+        for (y, line) in self.row.iter().enumerate() {
+            found.extend(memmem::find_iter(line, self.last_search.as_bytes()).map(|x| (x as u32, y as u32)));
+        }
+        if found.is_empty() {
+            let search = self.last_search.clone();
+            self.set_status_msg(format_args!("Not found: {}", search));
+            return;
+        }
+        let main = found.iter().position(|&(x, y)| (y, x) >= (self.cy, self.cx)).unwrap_or(0);
+        (self.cx, self.cy) = found.remove(main);
+        self.cursors = found;
+        let n = self.cursors.len() + 1;
+        self.set_status_msg(format_args!("{} cursors", n));
+    }
+
+    /* render columns of the extra cursors on row `y` */
+    pub(super) fn cursor_marks(&self, y: u32) -> Vec<usize> {
+        self.cursors.iter().filter(|c| c.1 == y).map(|c| self.cx_to_rx(y as usize, c.0) as usize).collect()
+    }
+
+    /* after the text was trimmed, no cursor or selection anchor past the end of its row */
+    pub(super) fn clamp_cursors(&mut self) {
+        for i in 0..self.cursors.len() {
+            let (x, y) = self.cursors[i];
+            self.cursors[i] = (x.min(self.row_len(y)), y.min(self.rows_num));
+        }
+        if let Some((x, y)) = self.sel_anchor {
+            self.sel_anchor = Some((x.min(self.row_len(y)), y.min(self.rows_num)));
+        }
+        self.tidy_cursors();
+    }
+
+    /* in file order, none twice and none where the main one is */
+    pub(super) fn tidy_cursors(&mut self) {
+        let main = (self.cx, self.cy);
+        self.cursors.sort_by_key(|&(x, y)| (y, x));
+        self.cursors.dedup();
+        self.cursors.retain(|c| *c != main);
+    }
+
+    fn row_len(&self, y: u32) -> u32 {
+        self.row.get(y as usize).map_or(0, |row| row.len() as u32)
+    }
+
+    /* the word the cursor is in or just after */
+    fn word_at_cursor(&self) -> Option<(usize, usize)> {
+        let line = self.row.get(self.cy as usize)?;
+        let mut start = self.cx as usize;
+        if !line.get(start).is_some_and(|c| util::is_word_char(*c)) {
+            start = start.checked_sub(1).filter(|x| util::is_word_char(line[*x]))?;
+        }
+        while start > 0 && util::is_word_char(line[start - 1]) {
+            start -= 1;
+        }
+        let mut end = start;
+        while end < line.len() && util::is_word_char(line[end]) {
+            end += 1;
+        }
+        Some((start, end))
+    }
+}
+
+/* where `word` stands on its own in `line` */
+fn find_words<'a>(line: &'a [u8], word: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    memmem::find_iter(line, word).filter(move |&x| {
+        (x == 0 || !util::is_word_char(line[x - 1]))
+            && line.get(x + word.len()).is_none_or(|c| !util::is_word_char(*c))
+    })
+}
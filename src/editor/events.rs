@@ -0,0 +1,172 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use termion::terminal_size;
+use crate::event::Wake;
+use crate::key::Event;
+use crate::terminal;
+use crate::watch::FileWatcher;
+use super::Editor;
+
+/* look at the file on disk this often, for when it is not being watched */
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Work the event loop wakes the editor up for, besides input.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(super) enum Task {
+    /// The status message is due to disappear.
+    StatusMsg,
+    Autosave,
+    /// Bring the swap file up to date.
+    Swap,
+    DiskCheck,
+    /// The file watcher has news.
+    FileChanged,
+}
+
+/* waiting for input, and everything that happens meanwhile */
+impl Editor {
+    /* the next input event, running timers and handling signals until one
+     * comes in; None once the terminal is gone. In a prompt, the file on disk
+     * is left to be checked after it, as reloading would pull the text from under it */
+    pub(super) fn wait_event(&mut self, in_prompt: bool) -> Option<Event> {
+        loop {
+            if self.stop_signal.is_some() {
+                return None;
+            }
+            if !in_prompt && self.disk_check_pending {
+                self.disk_check_pending = false;
+                self.check_disk();
+                self.refresh_screen();
+            }
+            match self.events.next() {
+                Wake::Input(event) => return Some(event),
+                Wake::Timer(task) | Wake::Readable(task) => self.run_task(task, in_prompt),
+                Wake::Signal(libc::SIGWINCH) => self.resize(),
+                Wake::Signal(libc::SIGCONT) => self.resume(),
+                Wake::Signal(signal @ (libc::SIGTERM | libc::SIGHUP)) => {
+                    self.stop_signal = Some(signal);
+                    return None;
+                }
+                Wake::Signal(_) => {}
+                Wake::Hangup => {
+                    self.stop_signal = Some(libc::SIGHUP);
+                    return None;
+                }
+            }
+            self.refresh_screen();
+        }
+    }
+
+    pub(super) fn start_tasks(&mut self) {
+        for signal in [libc::SIGWINCH, libc::SIGTERM, libc::SIGHUP, libc::SIGCONT] {
+            if let Err(e) = self.events.watch_signal(signal) {
+                self.set_status_msg(format_args!("Can't watch for signal {}: {}", signal, e));
+            }
+        }
+        self.events.set_timer(Task::DiskCheck, DISK_CHECK_INTERVAL);
+    }
+
+    fn run_task(&mut self, task: Task, in_prompt: bool) {
+        match task {
+            // the redraw after it leaves the message out
+            Task::StatusMsg => {}
+            Task::Autosave => self.autosave(),
+            Task::Swap => self.write_swap(),
+            Task::DiskCheck => {
+                self.check_disk_unless_prompting(in_prompt);
+                self.events.set_timer(Task::DiskCheck, DISK_CHECK_INTERVAL);
+            }
+            Task::FileChanged => {
+                if self.watcher.as_mut().is_some_and(|w| w.changed()) {
+                    self.check_disk_unless_prompting(in_prompt);
+                }
+            }
+        }
+    }
+
+    fn check_disk_unless_prompting(&mut self, in_prompt: bool) {
+        if in_prompt {
+            self.disk_check_pending = true;
+        } else {
+            self.check_disk();
+        }
+    }
+
+    /* Ctrl-Z: back to the shell until `fg` */
+    pub(super) fn suspend(&mut self) {
+        // the swap file has everything, should the editor never be continued
+        self.write_swap();
+        terminal::restore();
+        // the whole process group, as the shell would, so a pipeline stops together
+        unsafe { libc::kill(0, libc::SIGTSTP) };
+        // continued, or never stopped without a shell doing job control
+        self.resume();
+    }
+
+    /* continued after a stop, ours or anyone's: take the terminal back */
+    fn resume(&mut self) {
+        if let Err(e) = self.stdout.resume() {
+            self.set_status_msg(format_args!("Can't set up the terminal: {}", e));
+        }
+        if let Err(e) = self.stdout.set_alt_screen(self.cfg.alt_screen) {
+            self.set_status_msg(format_args!("Can't switch screens: {}", e));
+        }
+        // the window may have changed size while stopped
+        self.resize();
+    }
+
+    /* the window changed size */
+    fn resize(&mut self) {
+        let Ok((cols, rows)) = terminal_size() else { return };
+        for cfg in [&mut self.cfg, &mut self.user_cfg] {
+            cfg.screen_col = cols as u32;
+            cfg.screen_row = (rows as u32).saturating_sub(2).max(1);
+        }
+    }
+
+    /* follow the file being edited for changes as they happen */
+    pub(super) fn watch_file(&mut self) {
+        let path = PathBuf::from(&self.cfg.file_name);
+        if self.watcher.as_ref().is_some_and(|w| w.path() == path) {
+            return;
+        }
+        self.unwatch_file();
+        if let Some(watcher) = FileWatcher::new(&path) {
+            self.events.add_source(watcher.fd(), Task::FileChanged);
+            self.watcher = Some(watcher);
+        }
+    }
+
+    pub(super) fn unwatch_file(&mut self) {
+        if let Some(watcher) = self.watcher.take() {
+            self.events.remove_source(watcher.fd());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use crate::util::TempDir;
+    use super::*;
+    use super::super::test::editor;
+
+    #[test]
+    fn test_disk_check_in_prompt() {
+        let dir = TempDir::new();
+        let path = dir.path.join("a.txt");
+        fs::write(&path, b"one\ntwo\n").unwrap();
+        let mut e = editor(&dir, b"one\ntwo\n");
+        e.cfg.file_name = path.to_string_lossy().into_owned();
+        e.stamp_file(b"one\ntwo\n");
+        e.last_match = 1;
+
+        fs::write(&path, b"three\n").unwrap();
+        e.run_task(Task::DiskCheck, true);
+        assert!(e.disk_check_pending);
+        assert_eq!(2, e.rows_num);
+
+        e.reload(b"three\n");
+        assert_eq!((1, -1), (e.rows_num, e.last_match));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::config::{self, Value};
+use super::Editor;
+
+/* ex style command line */
+impl Editor {
+    /* prompt for a command and run it, returns false when the editor should exit */
+    pub(super) fn command_line(&mut self) -> bool {
+        let line = self.promotion_read(String::from(":{}"), |_, _, _| {});
+        self.ex_command(&line)
+    }
+
+    pub(super) fn ex_command(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        let result = match name {
+            "" => Ok(()),
+            "set" | "se" => self.ex_set(args),
+            "w" | "write" | "w!" | "write!" => {
+                if !args.is_empty() && args != self.cfg.file_name {
+                    self.set_file_name(args.to_string());
+                }
+                self.save_file(name.ends_with('!'));
+                Ok(())
+            }
+            "q" | "quit" if self.dirty => Err("No write since last change (add ! to override)".to_string()),
+            "q" | "quit" | "q!" | "quit!" => return false,
+            "n" | "next" | "n!" | "next!" => self.switch_file(1, name.ends_with('!')),
+            "N" | "prev" | "previous" | "N!" | "prev!" | "previous!" => self.switch_file(-1, name.ends_with('!')),
+            "e" | "edit" | "e!" | "edit!" if args.is_empty() => self.edit_again(name.ends_with('!')),
+            "args" => {
+                let list = self.file_list();
+                self.set_status_msg(format_args!("{}", list));
+                Ok(())
+            }
+            "retab" | "ret" if args.is_empty() => {
+                self.retab();
+                Ok(())
+            }
+            "reg" | "registers" => {
+                self.list_registers();
+                Ok(())
+            }
+            "wq" | "x" => {
+                self.save_file(false);
+                if !self.dirty {
+                    return false;
+                }
+                Ok(())
+            }
+            _ => Err(format!("Not an editor command: {}", line)),
+        };
+        if let Err(e) = result {
+            self.set_status_msg(format_args!("{}", e));
+        }
+        true
+    }
+
+    /* `set name=value`, `set name`, `set noname`, `set name!` and `set name?`, any number of them;
+       the ones that are right take effect even when another is not */
+    fn ex_set(&mut self, args: &str) -> Result<(), String> {
+        let format = (self.cfg.line_ending, self.cfg.final_newline, self.cfg.bom);
+        let mut shown = Vec::new();
+        let mut result = Ok(());
+        for arg in args.split_whitespace() {
+            result = result.and(self.set_arg(arg, &mut shown));
+        }
+        // converting the file format is a change to save
+        if format != (self.cfg.line_ending, self.cfg.final_newline, self.cfg.bom) {
+            self.dirty = true;
+            self.mixed_endings = false;
+        }
+        self.apply_settings();
+        if !shown.is_empty() {
+            self.set_status_msg(format_args!("{}", shown.join("  ")));
+        }
+        result
+    }
+
+    /* one argument of `set`, adding what it asks to see to `shown` */
+    fn set_arg(&mut self, arg: &str, shown: &mut Vec<String>) -> Result<(), String> {
+        if let Some((name, value)) = arg.split_once('=') {
+            self.set_option(name, &Value::parse_loose(value))
+        } else if let Some(name) = arg.strip_suffix('?') {
+            shown.push(format!("{}={}", name, value_to_string(&self.cfg.get(name)?)));
+            Ok(())
+        } else if let Some(name) = arg.strip_suffix('!') {
+            match self.cfg.get(name)? {
+                Value::Bool(b) => self.set_option(name, &Value::Bool(!b)),
+                _ => Err(format!("{} is not a boolean setting", name)),
+            }
+        } else {
+            match self.cfg.get(arg) {
+                Ok(Value::Bool(_)) => self.set_option(arg, &Value::Bool(true)),
+                Ok(value) => {
+                    shown.push(format!("{}={}", arg, value_to_string(&value)));
+                    Ok(())
+                }
+                Err(e) => match arg.strip_prefix("no") {
+                    Some(name) => self.set_option(name, &Value::Bool(false)),
+                    None => Err(e),
+                },
+            }
+        }
+    }
+
+    /* like vim's `:set`, a setting stays for the files edited next, unless it is about this file */
+    fn set_option(&mut self, name: &str, value: &Value) -> Result<(), String> {
+        self.cfg.set(name, value)?;
+        if !config::is_file_setting(name) {
+            self.user_cfg.set(name, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Bool(b) => b.to_string(),
+        Value::Int(n) => n.to_string(),
+        Value::Str(s) => s.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use crate::cli::FileArg;
+    use crate::util::TempDir;
+    use super::super::test::editor;
+
+    #[test]
+    fn test_set_stays_for_next_file() {
+        let dir = TempDir::new();
+        let mut e = editor(&dir, b"");
+        for name in ["a.txt", "b.txt"] {
+            let name = dir.path.join(name).to_string_lossy().into_owned();
+            fs::write(&name, b"text\n").unwrap();
+            e.files.push(FileArg { name, line: None, col: None });
+        }
+        e.open_file(0);
+        e.ex_command("set nonu ts=2 ro");
+        e.ex_command("n");
+        assert_eq!(1, e.file_index);
+        assert_eq!((false, 2, false), (e.cfg.line_numbers, e.cfg.tab_width, e.cfg.read_only));
+
+        let name = dir.path.join("c.py").to_string_lossy().into_owned();
+        e.ex_command(&format!("w {}", name));
+        assert_eq!(Some("python"), e.syntax.map(|s| s.file_type));
+        assert_eq!(b"text\n".to_vec(), fs::read(&name).unwrap());
+    }
+
+    #[test]
+    fn test_set_keeps_what_is_right() {
+        let dir = TempDir::new();
+        let mut e = editor(&dir, b"text\n");
+        e.ex_command("set ts=2 ts=99 nowrap bogus ff=dos nonu");
+        assert_eq!((2, false, false), (e.cfg.tab_width, e.cfg.soft_wrap, e.cfg.line_numbers));
+        assert_eq!("tab_width must be a number from 1 to 16", e.status_msg);
+        // the format still changed, which is a change to save
+        assert_eq!("crlf", e.format_status());
+        assert!(e.dirty);
+    }
+}
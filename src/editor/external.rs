@@ -0,0 +1,77 @@
+use std::cmp::min;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::fileio::{FileStamp, OnDisk};
+use super::Editor;
+
+/* other programs changing the file being edited */
+impl Editor {
+    /* remember the file as read or written, `content` being what is in it */
+    pub(super) fn stamp_file(&mut self, content: &[u8]) {
+        self.file_stamp = fs::metadata(&self.cfg.file_name).ok().map(|meta| FileStamp::new(&meta, content));
+        self.ignored_stamp = None;
+        self.watch_file();
+    }
+
+    /* on focus, on news from the watcher and every few seconds: warn about or reload a file changed on disk */
+    pub(super) fn check_disk(&mut self) {
+        let Some(stamp) = &self.file_stamp else { return };
+        match stamp.check(Path::new(&self.cfg.file_name)) {
+            Ok(OnDisk::Same) => {}
+            Ok(OnDisk::Touched(stamp)) => self.file_stamp = Some(stamp),
+            Ok(OnDisk::Changed(stamp, content)) => {
+                if self.ignored_stamp.as_ref() == Some(&stamp) {
+                    return;
+                }
+                if self.dirty {
+                    self.set_status_msg(format_args!("Warning: the file changed on disk, :e! loads it and drops your changes"));
+                } else if self.ask_yes_no("The file changed on disk. Load it? (y/n)") {
+                    self.reload(&content);
+                    return;
+                }
+                self.ignored_stamp = Some(stamp);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.file_stamp = None;
+                self.set_status_msg(format_args!("Warning: the file was deleted on disk"));
+            }
+            Err(_) => {}
+        }
+    }
+
+    /* before saving: whether someone else wrote the file since it was read */
+    pub(super) fn changed_on_disk(&self) -> bool {
+        let Some(stamp) = &self.file_stamp else { return false };
+        matches!(stamp.check(Path::new(&self.cfg.file_name)), Ok(OnDisk::Changed(..)))
+    }
+
+    /* `:e`, or `:e!` to drop the changes: read the file again */
+    pub(super) fn edit_again(&mut self, force: bool) -> Result<(), String> {
+        if self.cfg.file_name.is_empty() {
+            return Err("No file name".to_string());
+        }
+        if self.dirty && !force {
+            return Err("No write since last change (add ! to override)".to_string());
+        }
+        let content = fs::read(&self.cfg.file_name).map_err(|e| format!("Can't open! I/O error: {}", e))?;
+        self.reload(&content);
+        Ok(())
+    }
+
+    /* replace the text with the file's `content`, keeping the cursor where it was */
+    pub(super) fn reload(&mut self, content: &[u8]) {
+        let (cx, cy, row_off) = (self.cx, self.cy, self.row_off);
+        self.remove_swap();
+        self.reset_rows();
+        self.load_text(content);
+        self.stamp_file(content);
+        self.backed_up = false;
+
+        self.cy = min(cy, self.rows_num.saturating_sub(1));
+        self.cx = if self.cy < self.rows_num { min(cx, self.row[self.cy as usize].len() as u32) } else { 0 };
+        self.row_off = min(row_off, self.cy);
+        let name = self.cfg.file_name.clone();
+        self.set_status_msg(format_args!("\"{}\" reloaded", name));
+    }
+}
@@ -0,0 +1,176 @@
+use std::cmp::min;
+use crate::fileio;
+use super::Editor;
+
+/* the argument list, vim style: one buffer, refilled from the next file */
+impl Editor {
+    /* edit file `index` of the argument list, or an unnamed buffer when there is none */
+    pub(super) fn open_file(&mut self, index: usize) {
+        self.clear_buffer();
+        self.file_index = index;
+        let file = self.files.get(index).cloned();
+        if let Some(file) = &file {
+            self.cfg.file_name = file.name.clone();
+        }
+        self.edit_or_open();
+        if let Some(file) = file {
+            self.go_to(file.line, file.col);
+        }
+    }
+
+    /* edit text that has no file yet, read from stdin */
+    pub(super) fn open_text(&mut self, text: &[u8]) {
+        self.clear_buffer();
+        self.load_text(text);
+    }
+
+    /* fill the empty buffer, keeping the line breaks, final newline and BOM for saving
+       unless the file type or `.editorconfig` has settled them */
+    pub(super) fn load_text(&mut self, text: &[u8]) {
+        let (format, lines) = fileio::split_text(text);
+        for line in lines {
+            self.insert_new_row(self.rows_num as usize, line.to_vec());
+        }
+        self.update_in_comment();
+
+        let fixed = |name| self.fixed_format.contains(&name);
+        if let (Some(ending), false) = (format.line_ending, fixed("line_ending")) {
+            self.cfg.line_ending = ending;
+        }
+        if let (Some(final_newline), false) = (format.final_newline, fixed("final_newline")) {
+            self.cfg.final_newline = final_newline;
+        }
+        if !text.is_empty() && !fixed("bom") {
+            self.cfg.bom = format.bom;
+        }
+        self.mixed_endings = format.mixed && !fixed("line_ending");
+        self.dirty = false;
+    }
+
+    /* line break, missing final newline and BOM, for the status bar */
+    pub(super) fn format_status(&self) -> String {
+        let mut status = self.cfg.line_ending.name().to_string();
+        if self.mixed_endings {
+            status.push_str("[mixed]");
+        }
+        if !self.cfg.final_newline {
+            status.push_str(" noeol");
+        }
+        if self.cfg.bom {
+            status.push_str(" bom");
+        }
+        status
+    }
+
+    /* `step` files forward or back, refusing to drop changes unless `force` */
+    pub(super) fn switch_file(&mut self, step: isize, force: bool) -> Result<(), String> {
+        let index = self.file_index as isize + step;
+        if index < 0 {
+            return Err("Already at first file".to_string());
+        }
+        if index as usize >= self.files.len() {
+            return Err("Already at last file".to_string());
+        }
+        if self.dirty && self.cfg.autowrite && !force {
+            self.autosave();
+        }
+        if self.dirty && !force {
+            return Err("No write since last change (add ! to override)".to_string());
+        }
+        self.open_file(index as usize);
+        Ok(())
+    }
+
+    /* the argument list, the current file in brackets */
+    pub(super) fn file_list(&self) -> String {
+        let names: Vec<String> = self.files.iter().enumerate()
+            .map(|(i, f)| if i == self.file_index { format!("[{}]", f.name) } else { f.name.clone() })
+            .collect();
+        names.join(" ")
+    }
+
+    /* 1-based line and column, clamped to the text */
+    fn go_to(&mut self, line: Option<u32>, col: Option<u32>) {
+        if let Some(line) = line {
+            self.cy = min(line - 1, self.rows_num.saturating_sub(1));
+        }
+        if let (Some(col), true) = (col, self.cy < self.rows_num) {
+            self.cx = min(col - 1, self.row[self.cy as usize].len() as u32);
+        }
+    }
+
+    /* forget the current buffer and the settings its file brought */
+    fn clear_buffer(&mut self) {
+        self.remove_swap();
+        self.reset_rows();
+        self.dirty = false;
+
+        self.last_match = -1;
+        self.saved_match_hl.clear();
+        self.syntax = None;
+        self.mixed_endings = false;
+        self.fixed_format.clear();
+        self.clear_selection();
+        self.file_stamp = None;
+        self.ignored_stamp = None;
+        self.unwatch_file();
+        self.backed_up = false;
+
+        self.cfg = self.user_cfg.clone();
+        self.apply_settings();
+    }
+
+    /* no text, the cursor at the top */
+    pub(super) fn reset_rows(&mut self) {
+        self.cursors.clear();
+        self.clear_selection();
+        // no search match to put the highlighting back on
+        self.last_match = -1;
+        self.saved_match_hl.clear();
+        self.cx = 0;
+        self.rx = 0;
+        self.cy = 0;
+        self.row_off = 0;
+        self.col_off = 0;
+
+        self.rows_num = 0;
+        self.row.clear();
+        self.render.clear();
+        self.hl.clear();
+        self.in_comment.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use crate::cli::FileArg;
+    use crate::config::LineEnding;
+    use crate::util::TempDir;
+    use super::super::test::editor;
+
+    #[test]
+    fn test_editorconfig_format_wins() {
+        let dir = TempDir::new();
+        fs::write(dir.path.join(".editorconfig"), "root = true\n[*.txt]\nend_of_line = lf\ninsert_final_newline = true\ncharset = utf-8\n").unwrap();
+        let mut e = editor(&dir, b"");
+        for name in ["a.txt", "b.dat"] {
+            let name = dir.path.join(name).to_string_lossy().into_owned();
+            fs::write(&name, b"\xEF\xBB\xBFone\r\ntwo").unwrap();
+            e.files.push(FileArg { name, line: None, col: None });
+        }
+
+        e.open_file(0);
+        assert_eq!((LineEnding::Lf, true, false), (e.cfg.line_ending, e.cfg.final_newline, e.cfg.bom));
+        assert_eq!("lf", e.format_status());
+        e.save_file(false);
+        assert_eq!(b"one\ntwo\n".to_vec(), fs::read(&e.cfg.file_name).unwrap());
+
+        /* nothing set for this one, so it is saved the way it was */
+        e.open_file(1);
+        assert_eq!((LineEnding::CrLf, false, true), (e.cfg.line_ending, e.cfg.final_newline, e.cfg.bom));
+        e.dirty = true;
+        e.save_file(false);
+        assert_eq!(b"\xEF\xBB\xBFone\r\ntwo".to_vec(), fs::read(&e.cfg.file_name).unwrap());
+    }
+}
@@ -0,0 +1,248 @@
+use crate::highlight::Highlight;
+use crate::util;
+use super::Editor;
+
+/* automatic indentation */
+impl Editor {
+    /* Enter keeping the row's indentation, a level deeper after an opening
+       bracket, and with a closer right after the cursor on a row of its own */
+    pub(super) fn indent_new_line(&mut self) {
+        let (y, cx) = (self.cy as usize, self.cx as usize);
+        let base = self.row[y][..util::indent_len(&self.row[y])].to_vec();
+        // the last character before the cursor, a comment after it not counting
+        let last = (0..cx).rev().find(|&x| !self.row[y][x].is_ascii_whitespace() && !self.is_comment(y, x));
+        let opens = last.is_some_and(|x| self.is_code(y, x) && self.syntax.is_some_and(|s| s.indents_after(self.row[y][x])));
+
+        let mut tail = self.delete_content_to_end(y, cx);
+        // a blank row keeps no whitespace
+        if self.row[y][..cx].iter().all(|c| c.is_ascii_whitespace()) {
+            self.delete_content_to_end(y, 0);
+        }
+        tail.drain(..util::indent_len(&tail));
+        let closes = opens && tail.first().is_some_and(|c| self.syntax.is_some_and(|s| s.dedents_on(*c)));
+
+        let mut indent = base.clone();
+        if opens {
+            indent.extend(self.cfg.indent_unit());
+        }
+        self.cx = indent.len() as u32;
+        self.cy += 1;
+        if closes {
+            self.insert_new_row(y + 1, indent);
+            self.insert_new_row(y + 2, [base, tail].concat());
+        } else {
+            self.insert_new_row(y + 1, [indent, tail].concat());
+        }
+        self.dirty = true;
+        self.update_in_comment();
+    }
+
+    /* a closing bracket typed first on its row lines the row up with the row of its opener */
+    pub(super) fn dedent_for(&mut self, c: u8) {
+        if !self.cfg.auto_indent || !self.syntax.is_some_and(|s| s.dedents_on(c)) || self.cy >= self.rows_num {
+            return;
+        }
+        let (y, cx) = (self.cy as usize, self.cx as usize);
+        if util::indent_len(&self.row[y]) < cx {
+            return;
+        }
+        if let Some((_, open_y)) = self.opener_for(c) {
+            let open_row = &self.row[open_y as usize];
+            let indent = open_row[..util::indent_len(open_row)].to_vec();
+            self.cx = indent.len() as u32;
+            self.row[y].splice(..cx, indent);
+            self.update_render_and_hl(y);
+            self.dirty = true;
+        }
+    }
+
+    /* in the leading whitespace, delete back to the indent stop before the cursor, false elsewhere */
+    pub(super) fn delete_indent_level(&mut self) -> bool {
+        if self.cy >= self.rows_num {
+            return false;
+        }
+        let (y, cx) = (self.cy as usize, self.cx as usize);
+        if cx == 0 || util::indent_len(&self.row[y]) < cx {
+            return false;
+        }
+        let len = match self.row[y][cx - 1] {
+            b'\t' => 1,
+            _ => {
+                let rx = self.cx_to_rx(y, self.cx);
+                let stop = (rx - 1) / self.cfg.indent() * self.cfg.indent();
+                self.row[y][..cx].iter().rev().take_while(|c| **c == b' ').count().min((rx - stop) as usize)
+            }
+        };
+        self.row[y].drain(cx - len..cx);
+        self.update_render_and_hl(y);
+        self.cx -= len as u32;
+        self.dirty = true;
+        true
+    }
+
+    /* indent or outdent the selected rows, or the cursor's, by a level */
+    pub(super) fn shift_lines(&mut self, out: bool) {
+        match self.selected_rows() {
+            Some((top, bottom)) => self.shift_rows(top, bottom, out),
+            None if self.cy < self.rows_num => self.shift_rows(self.cy, self.cy, out),
+            None => {}
+        }
+    }
+
+    /* the rows of every cursor, each once */
+    pub(super) fn shift_lines_at_cursors(&mut self, out: bool) {
+        let mut done = None;
+        self.edit_at_cursors(|e| {
+            if done != Some(e.cy) {
+                done = Some(e.cy);
+                e.shift_lines(out);
+            }
+        });
+    }
+
+    pub(super) fn shift_rows(&mut self, top: u32, bottom: u32, out: bool) {
+        let indent = self.cfg.indent_unit();
+        for y in top..=bottom {
+            let row = &mut self.row[y as usize];
+            let delta = if out {
+                let len = match row.first() {
+                    Some(b'\t') => 1,
+                    _ => row.iter().take_while(|c| **c == b' ').count().min(self.cfg.indent() as usize),
+                };
+                row.drain(..len);
+                -(len as i64)
+            } else if !row.is_empty() {
+                row.splice(0..0, indent.iter().copied());
+                indent.len() as i64
+            } else {
+                0
+            };
+            if delta == 0 {
+                continue;
+            }
+            self.update_render_and_hl(y as usize);
+            // the selection keeps the same text, and all of the line when it started at its beginning
+            let shift = |pos: &mut (u32, u32)| if pos.1 == y && pos.0 > 0 { pos.0 = (pos.0 as i64 + delta).max(0) as u32 };
+            if let Some(anchor) = &mut self.sel_anchor {
+                shift(anchor);
+            }
+            let mut cursor = (self.cx, self.cy);
+            shift(&mut cursor);
+            self.cx = cursor.0;
+            self.dirty = true;
+        }
+    }
+
+    /* `retab`: the indentation of every row written as `expand_tabs` says */
+    pub(super) fn retab(&mut self) {
+        let mut changed = 0;
+        for y in 0..self.rows_num as usize {
+            if let Some(row) = util::retab(&self.row[y], self.cfg.tab_width, self.cfg.expand_tabs) {
+                self.row[y] = row;
+                self.update_render_and_hl(y);
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.dirty = true;
+            if self.cy < self.rows_num {
+                self.cx = self.cx.min(self.row[self.cy as usize].len() as u32);
+            }
+            self.clamp_cursors();
+        }
+        self.set_status_msg(format_args!("{} lines retabbed", changed));
+    }
+
+    /* the character at `x` of row `y` is code, not in a string or a comment */
+    pub(super) fn is_code(&self, y: usize, x: usize) -> bool {
+        self.is_code_at_rx(y, self.cx_to_rx(y, x as u32))
+    }
+
+    /* the same by render column, for scans that keep count of it */
+    pub(super) fn is_code_at_rx(&self, y: usize, rx: u32) -> bool {
+        !self.is_comment_at_rx(y, rx) && self.hl[y].get(rx as usize) != Some(&Highlight::String)
+    }
+
+    fn is_comment(&self, y: usize, x: usize) -> bool {
+        self.is_comment_at_rx(y, self.cx_to_rx(y, x as u32))
+    }
+
+    fn is_comment_at_rx(&self, y: usize, rx: u32) -> bool {
+        self.in_comment[y] || matches!(self.hl[y].get(rx as usize), Some(Highlight::Comment | Highlight::MComment))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::key::{Event, KeyCode, KeyEvent};
+    use crate::util::TempDir;
+    use super::super::Editor;
+    use super::super::test::editor;
+
+    fn type_keys(e: &mut Editor, keys: &str) {
+        for c in keys.chars() {
+            let code = if c == '\n' { KeyCode::Enter } else { KeyCode::Char(c) };
+            e.process_event(Event::Key(KeyEvent::plain(code)));
+        }
+    }
+
+    fn rows(e: &Editor) -> Vec<String> {
+        e.row[..e.rows_num as usize].iter().map(|row| String::from_utf8_lossy(row).into_owned()).collect()
+    }
+
+    fn c_editor(dir: &TempDir) -> Editor {
+        let mut e = editor(dir, b"");
+        e.cfg.file_name = "main.c".to_string();
+        e.select_syntax();
+        e.cfg.expand_tabs = true;
+        e.cfg.indent_width = 4;
+        e
+    }
+
+    #[test]
+    fn test_indent_after_opener() {
+        let dir = TempDir::new();
+        let mut e = c_editor(&dir);
+        type_keys(&mut e, "if (a) {\nb;\n");
+        assert_eq!(vec!["if (a) {", "    b;", "    "], rows(&e));
+
+        /* a comment after the opener does not count, nor does one inside it */
+        let mut e = c_editor(&dir);
+        type_keys(&mut e, "f(); // {\nx = \"{\";\ny;");
+        assert_eq!(vec!["f(); // {", "x = \"{\";", "y;"], rows(&e));
+    }
+
+    #[test]
+    fn test_dedent_on_closer() {
+        let dir = TempDir::new();
+        let mut e = c_editor(&dir);
+        type_keys(&mut e, "int f() {\nif (a) {\nb;\n}\n}");
+        assert_eq!(vec!["int f() {", "    if (a) {", "        b;", "    }", "}"], rows(&e));
+
+        /* on a row already at its opener's indent the closer stays there */
+        let mut e = c_editor(&dir);
+        type_keys(&mut e, "int f() {\nif (a) {\nb;\n");
+        e.delete_indent_level();
+        type_keys(&mut e, "}");
+        assert_eq!(vec!["int f() {", "    if (a) {", "        b;", "    }"], rows(&e));
+
+        /* between the brackets, Enter puts the closer on a row of its own */
+        let mut e = c_editor(&dir);
+        type_keys(&mut e, "{}");
+        e.cx = 1;
+        type_keys(&mut e, "\nx");
+        assert_eq!(vec!["{", "    x", "}"], rows(&e));
+    }
+
+    #[test]
+    fn test_makefile_recipe() {
+        let dir = TempDir::new();
+        let mut e = editor(&dir, b"");
+        e.cfg.file_name = "Makefile".to_string();
+        e.select_syntax();
+        e.apply_file_settings().unwrap();
+        /* a rule's recipe goes a tab in, its prerequisites are no opener */
+        type_keys(&mut e, "all: main\nmain:\ncc -o main main.c");
+        assert_eq!(vec!["all: main", "main:", "\tcc -o main main.c"], rows(&e));
+    }
+}
@@ -0,0 +1,151 @@
+use std::cmp::min;
+use std::time::{Duration, Instant};
+use crate::key::{MouseButton, MouseEvent, MouseKind};
+use super::{Editor, SelKind};
+
+/* two clicks on the same cell within this time make a double click */
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+/* rows moved by one step of the wheel */
+const SCROLL_ROWS: i64 = 3;
+
+/* mouse */
+impl Editor {
+    pub(super) fn process_mouse(&mut self, mouse: MouseEvent) {
+        match mouse.kind {
+            MouseKind::Press(MouseButton::Left) if mouse.row < self.cfg.screen_row => {
+                let Some((cx, cy)) = self.screen_to_file(mouse.col, mouse.row) else { return };
+                let now = Instant::now();
+                let double = matches!(self.last_click,
+                    Some((t, x, y)) if now.duration_since(t) < DOUBLE_CLICK && (x, y) == (cx, cy));
+
+                self.cx = cx;
+                self.cy = cy;
+                self.cursors.clear();
+                if double {
+                    self.select_word();
+                    self.last_click = None;
+                } else {
+                    self.visual = false;
+                    self.sel_anchor = Some((cx, cy));
+                    self.sel_kind = SelKind::Char;
+                    self.last_click = Some((now, cx, cy));
+                }
+            }
+            MouseKind::Drag(MouseButton::Left) if self.sel_anchor.is_some() => {
+                if let Some((cx, cy)) = self.screen_to_file(mouse.col, mouse.row) {
+                    self.cx = cx;
+                    self.cy = cy;
+                }
+            }
+            MouseKind::Release(MouseButton::Left) if self.sel_anchor == Some((self.cx, self.cy)) => {
+                self.sel_anchor = None;
+            }
+            MouseKind::ScrollUp => self.scroll_view(-SCROLL_ROWS),
+            MouseKind::ScrollDown => self.scroll_view(SCROLL_ROWS),
+            _ => {}
+        }
+    }
+
+    /* file position shown at a screen cell, past the end of the text snaps to the last row */
+    fn screen_to_file(&self, col: u32, row: u32) -> Option<(u32, u32)> {
+        if self.rows_num == 0 {
+            return None;
+        }
+        let lines = self.screen_lines();
+        let (cy, sub) = match lines[min(row, self.cfg.screen_row - 1) as usize] {
+            Some(line) => line,
+            None => (self.rows_num - 1, self.wrapped_rows(self.rows_num - 1) - 1),
+        };
+        let rx = col.saturating_sub(self.gutter_width()) + self.segment_start(sub);
+        Some((self.rx_to_cx(cy as usize, rx), cy))
+    }
+
+    /* move the view, dragging the cursor along only as far as needed to keep it on screen */
+    fn scroll_view(&mut self, delta: i64) {
+        if self.rows_num == 0 {
+            return;
+        }
+        let max_off = self.rows_num - 1;
+        self.row_off = (self.row_off as i64 + delta).clamp(0, max_off as i64) as u32;
+
+        let last = self.screen_lines().iter().flatten().last().map_or(self.row_off, |(y, _)| *y);
+        self.cy = self.cy.clamp(self.row_off, last);
+        self.cx = min(self.cx, self.row[self.cy as usize].len() as u32);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::key::Decoder;
+    use crate::keymap::Command;
+    use crate::util::TempDir;
+    use super::super::Editor;
+    use super::super::test::editor;
+
+    /* what a terminal reports: the mouse events in `reports`, decoded and handled */
+    fn mouse(e: &mut Editor, reports: &[u8]) {
+        let mut decoder = Decoder::default();
+        decoder.feed(reports);
+        while let Some(event) = decoder.next_event(false) {
+            e.process_event(event);
+        }
+    }
+
+    fn mouse_editor(dir: &TempDir, text: &[u8]) -> Editor {
+        let mut e = editor(dir, text);
+        e.cfg.line_numbers = false;
+        (e.cfg.screen_col, e.cfg.screen_row) = (40, 10);
+        e
+    }
+
+    fn copied(e: &mut Editor) -> Vec<u8> {
+        e.execute(Command::Copy);
+        e.registers.get('0').unwrap().text.clone()
+    }
+
+    #[test]
+    fn test_click_and_drag() {
+        let dir = TempDir::new();
+        let mut e = mouse_editor(&dir, b"one two\nthree\n");
+        mouse(&mut e, b"\x1b[<0;3;2M\x1b[<0;3;2m");
+        assert_eq!((2, 1), (e.cx, e.cy));
+        assert!(e.sel_anchor.is_none());
+
+        // past the end of a row or of the text
+        mouse(&mut e, b"\x1b[<0;30;1M\x1b[<0;30;1m");
+        assert_eq!((7, 0), (e.cx, e.cy));
+        mouse(&mut e, b"\x1b[<0;2;9M\x1b[<0;2;9m");
+        assert_eq!((1, 1), (e.cx, e.cy));
+
+        mouse(&mut e, b"\x1b[<0;2;1M\x1b[<32;3;1M\x1b[<32;4;2M\x1b[<0;4;2m");
+        assert_eq!((Some((1, 0)), (3, 1)), (e.sel_anchor, (e.cx, e.cy)));
+        assert_eq!(b"ne two\nthr".to_vec(), copied(&mut e));
+    }
+
+    #[test]
+    fn test_double_click() {
+        let dir = TempDir::new();
+        let mut e = mouse_editor(&dir, b"one two\n");
+        mouse(&mut e, b"\x1b[<0;6;1M\x1b[<0;6;1m\x1b[<0;6;1M\x1b[<0;6;1m");
+        assert_eq!(b"two".to_vec(), copied(&mut e));
+
+        // on another cell it is two clicks
+        mouse(&mut e, b"\x1b[<0;6;1M\x1b[<0;6;1m\x1b[<0;2;1M\x1b[<0;2;1m");
+        assert!(e.sel_anchor.is_none());
+        assert_eq!((1, 0), (e.cx, e.cy));
+    }
+
+    #[test]
+    fn test_wheel() {
+        let dir = TempDir::new();
+        let mut e = mouse_editor(&dir, "x\n".repeat(30).as_bytes());
+        mouse(&mut e, b"\x1b[<65;1;1M");
+        assert_eq!((3, 3), (e.row_off, e.cy));
+        mouse(&mut e, b"\x1b[<65;1;1M".repeat(20).as_slice());
+        assert_eq!((29, 29), (e.row_off, e.cy));
+
+        // scrolling back drags the cursor only to the bottom of the screen
+        mouse(&mut e, b"\x1b[<64;1;1M".repeat(5).as_slice());
+        assert_eq!((14, 23), (e.row_off, e.cy));
+    }
+}
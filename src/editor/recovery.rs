@@ -0,0 +1,244 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+use crate::diff;
+use crate::fileio;
+use crate::key::KeyCode;
+use crate::swap::{self, Swap};
+use super::{Editor, Task};
+
+/* write the swap file after this many keys, or this long after a change it is missing */
+const SWAP_KEYS: u32 = 200;
+const SWAP_INTERVAL: Duration = Duration::from_secs(4);
+
+/* swap files and recovering from them */
+impl Editor {
+    /* called after each key: keep the swap file up to date while the buffer is modified */
+    pub(super) fn update_swap(&mut self) {
+        if !self.dirty {
+            self.remove_swap();
+            return;
+        }
+        if !self.cfg.swap_file || self.cfg.file_name.is_empty() {
+            return;
+        }
+        self.swap_keys += 1;
+        if self.swap_written.is_none() || self.swap_keys >= SWAP_KEYS {
+            self.write_swap();
+        } else if !self.events.has_timer(Task::Swap) {
+            self.events.set_timer(Task::Swap, SWAP_INTERVAL);
+        }
+    }
+
+    pub(super) fn write_swap(&mut self) {
+        self.events.cancel_timer(Task::Swap);
+        if !self.dirty || !self.cfg.swap_file || self.cfg.file_name.is_empty() {
+            return;
+        }
+        let Some(path) = swap::swap_path(Path::new(&self.cfg.file_name)) else { return };
+        match swap::write(&path, self.cx, self.cy, &self.file_content()) {
+            Ok(()) => {
+                self.swap_written = Some(Instant::now());
+                self.swap_keys = 0;
+            }
+            Err(e) => {
+                self.cfg.swap_file = false;
+                self.set_status_msg(format_args!("Can't write swap file, no more tries: {}", e));
+            }
+        }
+    }
+
+    /* the buffer was saved or thrown away, its swap file is no longer needed */
+    pub(super) fn remove_swap(&mut self) {
+        self.events.cancel_timer(Task::Swap);
+        if self.swap_written.take().is_none() {
+            return;
+        }
+        if let Some(path) = swap::swap_path(Path::new(&self.cfg.file_name)) {
+            let _ = fs::remove_file(path);
+        }
+        self.swap_keys = 0;
+    }
+
+    /* the editor is going down before the changes could be saved: put them
+     * where they can be got back, returns what to tell the user */
+    pub(super) fn emergency_dump(&mut self) -> Option<String> {
+        if !self.dirty {
+            return None;
+        }
+        let text = self.file_content();
+        // without swap files, or when the swap file is another editor's, a file of its own
+        let swap_path = Some(&self.cfg.file_name)
+            .filter(|name| self.cfg.swap_file && !name.is_empty())
+            .and_then(|name| swap::swap_path(Path::new(name)))
+            .filter(|path| !swap::read(path).is_ok_and(|swap| swap.is_running()));
+        let use_swap = swap_path.is_some();
+        let Some(path) = swap_path.or_else(swap::recovery_path) else {
+            return Some("Unsaved changes lost: no state directory".to_string());
+        };
+        let written = if use_swap { swap::write(&path, self.cx, self.cy, &text) } else { swap::write_recovery(&path, &text) };
+        Some(match written {
+            Ok(()) if use_swap => format!("Unsaved changes kept in {}, open {} again to recover them",
+                path.display(), self.cfg.file_name),
+            Ok(()) => format!("Unsaved changes written to {}", path.display()),
+            Err(e) => format!("Can't write unsaved changes to {}: {}", path.display(), e),
+        })
+    }
+
+    /* after opening a file: offer the text of a swap file left behind for it */
+    pub(super) fn check_swap(&mut self, original: &[u8]) {
+        if !self.cfg.swap_file || self.cfg.file_name.is_empty() {
+            return;
+        }
+        let Some(path) = swap::swap_path(Path::new(&self.cfg.file_name)) else { return };
+        let swap = match swap::read(&path) {
+            Ok(swap) => swap,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                self.set_status_msg(format_args!("Can't read swap file {}: {}", path.display(), e));
+                return;
+            }
+        };
+
+        let age = SystemTime::now().duration_since(swap.modified).unwrap_or_default();
+        // the swap file of an editor still running is that editor's to write and remove
+        let running = swap.is_running();
+        let question = if running {
+            format!("Swap file found (pid {}, STILL RUNNING, {} old): [d]iff [o]pen read-only", swap.pid, format_age(age))
+        } else {
+            format!("Swap file found (pid {}, {} old): [r]ecover [d]iff [D]elete [o]pen read-only", swap.pid, format_age(age))
+        };
+        let syntax = self.syntax;
+        loop {
+            match self.ask(&question).code {
+                KeyCode::Char('r') if !running => {
+                    self.syntax = syntax;
+                    self.recover(&swap);
+                    return;
+                }
+                KeyCode::Char('d') => self.show_swap_diff(original, &swap.text),
+                KeyCode::Char('D') if !running => {
+                    let _ = fs::remove_file(&path);
+                    self.syntax = syntax;
+                    self.reset_rows();
+                    self.load_text(original);
+                    self.set_status_msg(format_args!("Swap file deleted"));
+                    return;
+                }
+                KeyCode::Char('o') | KeyCode::Esc => {
+                    self.syntax = syntax;
+                    self.reset_rows();
+                    self.load_text(original);
+                    // leave the swap file for whoever recovers it
+                    self.cfg.read_only = true;
+                    self.cfg.swap_file = false;
+                    self.set_status_msg(format_args!(""));
+                    return;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /* the text and cursor of `swap` in the buffer, still to be saved */
+    fn recover(&mut self, swap: &Swap) {
+        self.reset_rows();
+        self.load_text(&swap.text);
+        self.cy = swap.cy.min(self.rows_num.saturating_sub(1));
+        self.cx = if self.cy < self.rows_num { swap.cx.min(self.row[self.cy as usize].len() as u32) } else { 0 };
+        self.dirty = true;
+        // the swap file is this buffer's now, removed once it is saved
+        self.swap_written = Some(Instant::now());
+        self.set_status_msg(format_args!("Recovered, save to keep the text"));
+    }
+
+    /* put the changes from the file to the swap file in the buffer, to be looked at */
+    fn show_swap_diff(&mut self, original: &[u8], text: &[u8]) {
+        let (_, old) = fileio::split_text(original);
+        let (_, new) = fileio::split_text(text);
+        let lines = diff::format_hunks(&diff::diff_lines(&old, &new), 3);
+
+        self.syntax = None;
+        self.reset_rows();
+        if lines.is_empty() {
+            self.insert_new_row(0, b"(the swap file has the same text as the file)".to_vec());
+        }
+        for line in lines {
+            self.insert_new_row(self.rows_num as usize, line);
+        }
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86400 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86400),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use crate::key::Input;
+    use crate::keymap::Command;
+    use super::*;
+    use crate::util::TempDir;
+    use super::super::test::editor;
+
+    #[test]
+    fn test_dump_and_recover() {
+        let dir = TempDir::new();
+        let name = dir.path.join("dump.txt").to_string_lossy().into_owned();
+        let mut e = editor(&dir, b"one\ntwo\n");
+        e.cfg.file_name = name.clone();
+        e.execute(Command::MoveDown);
+        e.execute(Command::LineEnd);
+        e.insert_char(b'!');
+        let msg = e.emergency_dump().unwrap();
+        let path = swap::swap_path(Path::new(&name)).unwrap();
+        assert!(msg.contains(&path.display().to_string()), "{}", msg);
+
+        let mut restored = editor(&dir, b"one\ntwo\n");
+        restored.cfg.file_name = name.clone();
+        restored.recover(&swap::read(&path).unwrap());
+        assert_eq!((e.row.clone(), (e.cx, e.cy)), (restored.row.clone(), (restored.cx, restored.cy)));
+        assert!(restored.dirty);
+
+        // another editor running with the file: its swap file is left alone
+        let other = format!("my_vim swap pid={} cursor=0,0\ntheirs\n", std::os::unix::process::parent_id());
+        fs::write(&path, &other).unwrap();
+        let msg = e.emergency_dump().unwrap();
+        assert_eq!(other.as_bytes(), &fs::read(&path).unwrap()[..]);
+        let recovery = msg.strip_prefix("Unsaved changes written to ").unwrap();
+        assert_eq!(b"one\ntwo!\n", &fs::read(recovery).unwrap()[..]);
+    }
+
+    #[test]
+    fn test_live_swap_left_alone() {
+        let dir = TempDir::new();
+        let mut e = editor(&dir, b"");
+        let name = dir.path.join("live.txt").to_string_lossy().into_owned();
+        let path = swap::swap_path(Path::new(&name)).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let theirs = format!("my_vim swap pid={} cursor=0,0\ntheirs\n", std::os::unix::process::parent_id());
+        fs::write(&path, &theirs).unwrap();
+
+        // recovering and deleting are no answers while its editor runs
+        let mut fds = [0; 2];
+        assert_eq!(0, unsafe { libc::pipe(fds.as_mut_ptr()) });
+        assert_eq!(3, unsafe { libc::write(fds[1], b"rDo".as_ptr() as *const libc::c_void, 3) });
+        e.events.input = Input::from_fd(fds[0], Duration::from_millis(50));
+        e.cfg.file_name = name;
+        e.check_swap(b"mine\n");
+        assert_eq!(vec![b"mine".to_vec()], e.row);
+        assert!(e.cfg.read_only && !e.dirty);
+        assert_eq!(theirs.as_bytes(), &fs::read(&path).unwrap()[..]);
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+}
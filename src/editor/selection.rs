@@ -0,0 +1,269 @@
+use std::cmp::{max, min};
+use std::iter;
+use crate::util;
+use super::Editor;
+
+/// What a selection covers between its anchor and the cursor.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(super) enum SelKind {
+    /// The characters from one to the other.
+    Char,
+    /// Whole lines, from the anchor's line to the cursor's.
+    Line,
+    /// A rectangle of display columns, between the anchor's column and the cursor's.
+    Block,
+}
+
+/* selection */
+impl Editor {
+    /* ends of the selection as ordered (cx, cy) pairs, the end is exclusive */
+    pub(super) fn selection(&self) -> Option<((u32, u32), (u32, u32))> {
+        let anchor = self.sel_anchor?;
+        let cursor = (self.cx, self.cy);
+        match self.sel_kind {
+            SelKind::Char if anchor == cursor => None,
+            SelKind::Char if (anchor.1, anchor.0) < (cursor.1, cursor.0) => Some((anchor, cursor)),
+            SelKind::Char => Some((cursor, anchor)),
+            SelKind::Line => {
+                if self.rows_num == 0 {
+                    return None;
+                }
+                let top = min(anchor.1, cursor.1);
+                let bottom = min(max(anchor.1, cursor.1), self.rows_num - 1);
+                let end = if bottom + 1 < self.rows_num {
+                    (0, bottom + 1)
+                } else {
+                    (self.row[bottom as usize].len() as u32, bottom)
+                };
+                Some(((0, top), end))
+            }
+            SelKind::Block => {
+                let (top, bottom, left, right) = self.block().filter(|b| b.2 < b.3)?;
+                Some(((self.block_span(top, left, right).0 as u32, top), (self.block_span(bottom, left, right).1 as u32, bottom)))
+            }
+        }
+    }
+
+    /* a block selection's first and last row, and the render columns from `left` up to `right` */
+    pub(super) fn block(&self) -> Option<(u32, u32, u32, u32)> {
+        let anchor = self.sel_anchor?;
+        if self.sel_kind != SelKind::Block || self.rows_num == 0 {
+            return None;
+        }
+        let from = if anchor.1 < self.rows_num { self.cx_to_rx(anchor.1 as usize, anchor.0) } else { 0 };
+        // past the end of a short row the block goes on to the column the cursor keeps to
+        let to = self.want_rx();
+        let top = min(anchor.1, self.cy);
+        let bottom = min(max(anchor.1, self.cy), self.rows_num - 1);
+        Some((top, bottom, min(from, to), max(from, to)))
+    }
+
+    /* the characters of row `y` shown from render column `left` up to `right`, a tab partly inside counting */
+    pub(super) fn block_span(&self, y: u32, left: u32, right: u32) -> (usize, usize) {
+        let from = self.rx_to_cx(y as usize, left) as usize;
+        let to = self.rx_to_cx(y as usize, right) as usize;
+        (from, max(from, to))
+    }
+
+    /* the block's rows joined by `\n` */
+    pub(super) fn block_text(&self) -> Option<Vec<u8>> {
+        let (top, bottom, left, right) = self.block()?;
+        let rows: Vec<&[u8]> = (top..=bottom).map(|y| {
+            let (from, to) = self.block_span(y, left, right);
+            &self.row[y as usize][from..to]
+        }).collect();
+        Some(rows.join(&b'\n'))
+    }
+
+    /* a cursor on every row of the block at its left edge, or at its right edge with short rows padded */
+    pub(super) fn block_cursors(&mut self, append: bool) {
+        let Some((top, bottom, left, right)) = self.block() else {
+            self.set_status_msg(format_args!("No block selected"));
+            return;
+        };
+        let col = if append { right } else { left };
+        let mut cursors = Vec::new();
+        for y in top..=bottom {
+            let len = self.render[y as usize].len() as u32;
+            if len < col {
+                if !append {
+                    continue;
+                }
+                self.row[y as usize].extend(iter::repeat_n(b' ', (col - len) as usize));
+                self.update_render_and_hl(y as usize);
+                self.dirty = true;
+            }
+            cursors.push((self.rx_to_cx(y as usize, col), y));
+        }
+        self.clear_selection();
+        if cursors.is_empty() {
+            return;
+        }
+        (self.cx, self.cy) = cursors.remove(0);
+        self.cursors = cursors;
+    }
+
+    /* the text from `start` up to `end`, rows joined by `\n` */
+    pub(super) fn text_between(&self, start: (u32, u32), end: (u32, u32)) -> Vec<u8> {
+        if start.1 == end.1 {
+            return self.row[start.1 as usize][start.0 as usize..end.0 as usize].to_vec();
+        }
+        let mut text = self.row[start.1 as usize][start.0 as usize..].to_vec();
+        for y in start.1 + 1..end.1 {
+            text.push(b'\n');
+            text.extend_from_slice(&self.row[y as usize]);
+        }
+        text.push(b'\n');
+        text.extend_from_slice(&self.row[end.1 as usize][..end.0 as usize]);
+        text
+    }
+
+    /* first and last row the selection touches, not counting a row it ends at the start of */
+    pub(super) fn selected_rows(&self) -> Option<(u32, u32)> {
+        let (start, end) = self.selection()?;
+        let last = if end.0 == 0 && end.1 > start.1 && self.sel_kind != SelKind::Block { end.1 - 1 } else { end.1 };
+        Some((start.1, last))
+    }
+
+    /* Shift+motion or visual mode: anchor a selection at the cursor, unless one is going */
+    pub(super) fn start_selection(&mut self, kind: SelKind) {
+        if self.sel_anchor.is_none() {
+            self.sel_anchor = Some((self.cx, self.cy));
+            self.sel_kind = kind;
+        }
+    }
+
+    pub(super) fn clear_selection(&mut self) {
+        self.sel_anchor = None;
+        self.visual = false;
+    }
+
+    /* enter visual mode, switch its kind, or leave it when it is that kind already */
+    pub(super) fn toggle_visual(&mut self, kind: SelKind) {
+        if self.visual && self.sel_kind == kind {
+            self.clear_selection();
+            return;
+        }
+        self.start_selection(kind);
+        self.sel_kind = kind;
+        self.visual = true;
+    }
+
+    /* remove the selected text and end the selection, false when there was no text selected */
+    pub(super) fn delete_selection(&mut self) -> bool {
+        if let (SelKind::Block, Some(_)) = (self.sel_kind, self.selection()) {
+            let (top, bottom, left, right) = self.block().unwrap();
+            for y in top..=bottom {
+                let (from, to) = self.block_span(y, left, right);
+                self.row[y as usize].drain(from..to);
+                self.update_render_and_hl(y as usize);
+            }
+            (self.cx, self.cy) = (self.rx_to_cx(top as usize, left), top);
+            self.clear_selection();
+            self.dirty = true;
+            self.update_in_comment();
+            return true;
+        }
+        let Some((mut start, end)) = self.selection() else {
+            self.clear_selection();
+            return false;
+        };
+        // lines to the end of the text: the line break before them goes too
+        let to_last_row = self.sel_anchor.is_some_and(|(_, y)| max(y, self.cy) + 1 >= self.rows_num);
+        if self.sel_kind == SelKind::Line && to_last_row && start.1 > 0 {
+            start = (self.row[start.1 as usize - 1].len() as u32, start.1 - 1);
+        }
+
+        let tail = self.row[end.1 as usize][end.0 as usize..].to_vec();
+        for _ in start.1..end.1 {
+            self.delete_row(start.1 as usize + 1);
+        }
+        self.delete_content_to_end(start.1 as usize, start.0 as usize);
+        self.insert_line_to_row(start.1 as usize, tail);
+
+        (self.cx, self.cy) = start;
+        self.clear_selection();
+        self.dirty = true;
+        self.update_in_comment();
+        true
+    }
+
+    /* remove the selected block leaving a cursor on each of its rows, false without one */
+    pub(super) fn change_block(&mut self) -> bool {
+        let Some((top, bottom, left, _)) = self.block().filter(|_| self.selection().is_some()) else { return false };
+        self.delete_selection();
+        self.cursors = (top + 1..=bottom)
+            .filter(|&y| self.render[y as usize].len() as u32 >= left)
+            .map(|y| (self.rx_to_cx(y as usize, left), y))
+            .collect();
+        true
+    }
+
+    /* upper or lower case the selected letters */
+    pub(super) fn change_case(&mut self, upper: bool) {
+        let Some((start, end)) = self.selection() else {
+            self.set_status_msg(format_args!("Nothing selected"));
+            return;
+        };
+        let block = self.block();
+        for y in start.1..=end.1 {
+            let (from, to) = match block {
+                Some((_, _, left, right)) => self.block_span(y, left, right),
+                None if y == end.1 => (if y == start.1 { start.0 as usize } else { 0 }, end.0 as usize),
+                None => (if y == start.1 { start.0 as usize } else { 0 }, self.row[y as usize].len()),
+            };
+            for c in &mut self.row[y as usize][from..to] {
+                *c = if upper { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() };
+            }
+            self.update_render_and_hl(y as usize);
+        }
+        self.dirty = true;
+    }
+
+    /* Tab with a selection: indent the rows it touches, false without one */
+    pub(super) fn indent_selection(&mut self) -> bool {
+        let Some((top, bottom)) = self.selected_rows() else { return false };
+        self.shift_rows(top, bottom, false);
+        true
+    }
+
+    /* render columns of row `y` covered by the selection */
+    pub(super) fn selected_render_range(&self, y: u32) -> (usize, usize) {
+        match self.selection() {
+            Some((start, end)) if start.1 <= y && y <= end.1 && self.sel_kind == SelKind::Block => {
+                let (_, _, left, right) = self.block().unwrap();
+                let (from, to) = self.block_span(y, left, right);
+                (self.cx_to_rx(y as usize, from as u32) as usize, self.cx_to_rx(y as usize, to as u32) as usize)
+            }
+            Some((start, end)) if start.1 <= y && y <= end.1 => {
+                let from = if y == start.1 { self.cx_to_rx(y as usize, start.0) } else { 0 };
+                let to = if y == end.1 {
+                    self.cx_to_rx(y as usize, end.0)
+                } else {
+                    self.render[y as usize].len() as u32
+                };
+                (from as usize, to as usize)
+            }
+            _ => (0, 0),
+        }
+    }
+
+    /* select the word under the cursor */
+    pub(super) fn select_word(&mut self) {
+        let line = &self.row[self.cy as usize];
+        let mut start = self.cx as usize;
+        let mut end = start;
+        if end >= line.len() || !util::is_word_char(line[end]) {
+            return;
+        }
+        while start > 0 && util::is_word_char(line[start - 1]) {
+            start -= 1;
+        }
+        while end < line.len() && util::is_word_char(line[end]) {
+            end += 1;
+        }
+        self.sel_anchor = Some((start as u32, self.cy));
+        self.sel_kind = SelKind::Char;
+        self.cx = end as u32;
+    }
+}
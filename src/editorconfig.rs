@@ -0,0 +1,316 @@
+use std::fs;
+use std::path::{self, Path};
+use crate::config::Value;
+
+/// One `.editorconfig` file: whether it stops the search, and its
+/// `[glob]` sections with their `key = value` pairs.
+struct EditorConfig {
+    root: bool,
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl EditorConfig {
+    /// Keys and values are case insensitive, so both are lowercased.
+    fn parse(text: &str) -> EditorConfig {
+        let mut config = EditorConfig { root: false, sections: Vec::new() };
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                config.sections.push((line[1..line.len() - 1].to_string(), Vec::new()));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else { continue };
+            let (key, value) = (key.trim().to_ascii_lowercase(), value.trim().to_ascii_lowercase());
+            match config.sections.last_mut() {
+                Some((_, pairs)) => pairs.push((key, value)),
+                None => config.root |= key == "root" && value == "true",
+            }
+        }
+        config
+    }
+
+    /// Set the properties of the sections matching `path`, relative to this file's directory.
+    fn apply(&self, path: &str, props: &mut Vec<(String, String)>) {
+        for (glob, pairs) in &self.sections {
+            // a glob without a slash matches the file name in any directory
+            let glob = match glob.strip_prefix('/') {
+                Some(glob) => glob.to_string(),
+                None if glob.contains('/') => glob.clone(),
+                None => format!("**/{}", glob),
+            };
+            if !glob_match(&glob, path) {
+                continue;
+            }
+            for (key, value) in pairs {
+                props.retain(|(k, _)| k != key);
+                if value != "unset" {
+                    props.push((key.clone(), value.clone()));
+                }
+            }
+        }
+    }
+}
+
+/// The properties for the file at `path` from the `.editorconfig` files in
+/// its directory and those above, up to one marked `root = true`. Nearer
+/// files win.
+pub fn properties(path: &Path) -> Vec<(String, String)> {
+    let mut props = Vec::new();
+    let Ok(path) = path::absolute(path) else { return props };
+
+    let mut configs = Vec::new();
+    for dir in path.ancestors().skip(1) {
+        if let Ok(text) = fs::read_to_string(dir.join(".editorconfig")) {
+            let config = EditorConfig::parse(&text);
+            let root = config.root;
+            configs.push((dir, config));
+            if root {
+                break;
+            }
+        }
+    }
+
+    for (dir, config) in configs.iter().rev() {
+        if let Ok(rel) = path.strip_prefix(dir) {
+            config.apply(&rel.to_string_lossy(), &mut props);
+        }
+    }
+    props
+}
+
+/// Editor settings for EditorConfig properties. Values the editor has no
+/// setting for are left out.
+pub fn settings(props: &[(String, String)]) -> Vec<(&'static str, Value)> {
+    let get = |key: &str| props.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+    let number = |key: &str| get(key).and_then(|v| v.parse::<i64>().ok());
+    let mut settings = Vec::new();
+
+    match get("indent_style") {
+        Some("tab") => settings.push(("expand_tabs", Value::Bool(false))),
+        Some("space") => settings.push(("expand_tabs", Value::Bool(true))),
+        _ => {}
+    }
+    match (get("indent_size"), number("indent_size")) {
+        (Some("tab"), _) => settings.push(("indent_width", Value::Int(0))),
+        (_, Some(n)) => settings.push(("indent_width", Value::Int(n))),
+        _ => {}
+    }
+    // tab_width defaults to indent_size
+    if let Some(n) = number("tab_width").or_else(|| number("indent_size")) {
+        settings.push(("tab_width", Value::Int(n)));
+    }
+    if let Some(ending @ ("lf" | "crlf" | "cr")) = get("end_of_line") {
+        settings.push(("line_ending", Value::Str(ending.to_string())));
+    }
+    match get("charset") {
+        Some("utf-8-bom") => settings.push(("bom", Value::Bool(true))),
+        Some("utf-8" | "latin1") => settings.push(("bom", Value::Bool(false))),
+        _ => {}
+    }
+    for (key, name) in [("trim_trailing_whitespace", "trim_trailing_whitespace"), ("insert_final_newline", "final_newline")] {
+        match get(key) {
+            Some("true") => settings.push((name, Value::Bool(true))),
+            Some("false") => settings.push((name, Value::Bool(false))),
+            _ => {}
+        }
+    }
+    settings
+}
+
+/* EditorConfig globs: `*` and `?` stop at `/`, `**` does not, `[a-z]`,
+   `[!a-z]`, `{a,b}` and `{1..10}` */
+#[derive(Clone, Debug)]
+enum Token {
+    Char(char),
+    AnyChar,
+    Star,
+    DoubleStar,
+    Class(bool, Vec<(char, char)>),
+    Alt(Vec<Vec<Token>>),
+    Range(i64, i64),
+}
+
+fn glob_match(glob: &str, path: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    let mut i = 0;
+    let tokens = parse_glob(&glob, &mut i, false);
+    match_tokens(&tokens, &path)
+}
+
+/* parse up to the end, or inside braces up to the next `,` or `}` */
+fn parse_glob(glob: &[char], i: &mut usize, in_braces: bool) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    while *i < glob.len() {
+        let c = glob[*i];
+        if in_braces && (c == ',' || c == '}') {
+            break;
+        }
+        *i += 1;
+        match c {
+            '\\' if *i < glob.len() => {
+                tokens.push(Token::Char(glob[*i]));
+                *i += 1;
+            }
+            '?' => tokens.push(Token::AnyChar),
+            '*' if glob.get(*i) == Some(&'*') => {
+                *i += 1;
+                tokens.push(Token::DoubleStar);
+            }
+            '*' => tokens.push(Token::Star),
+            '[' => match parse_class(glob, i) {
+                Some(class) => tokens.push(class),
+                None => tokens.push(Token::Char('[')),
+            },
+            '{' => match parse_braces(glob, i) {
+                Some(alt) => tokens.push(alt),
+                None => tokens.push(Token::Char('{')),
+            },
+            c => tokens.push(Token::Char(c)),
+        }
+    }
+    tokens
+}
+
+/* after `[`, leaves `i` alone when there is no closing `]` */
+fn parse_class(glob: &[char], i: &mut usize) -> Option<Token> {
+    let mut j = *i;
+    let negate = glob.get(j) == Some(&'!');
+    if negate {
+        j += 1;
+    }
+    let mut ranges = Vec::new();
+    while j < glob.len() && glob[j] != ']' {
+        if j + 2 < glob.len() && glob[j + 1] == '-' && glob[j + 2] != ']' {
+            ranges.push((glob[j], glob[j + 2]));
+            j += 3;
+        } else {
+            ranges.push((glob[j], glob[j]));
+            j += 1;
+        }
+    }
+    if j >= glob.len() {
+        return None;
+    }
+    *i = j + 1;
+    Some(Token::Class(negate, ranges))
+}
+
+/* after `{`, leaves `i` alone when there is no closing `}` */
+fn parse_braces(glob: &[char], i: &mut usize) -> Option<Token> {
+    let close = glob[*i..].iter().position(|c| *c == '}')? + *i;
+    let inner: String = glob[*i..close].iter().collect();
+    if let Some((lo, hi)) = inner.split_once("..") {
+        if let (Ok(lo), Ok(hi)) = (lo.parse(), hi.parse()) {
+            *i = close + 1;
+            return Some(Token::Range(lo, hi));
+        }
+    }
+
+    let mut j = *i;
+    let mut alts = Vec::new();
+    loop {
+        alts.push(parse_glob(glob, &mut j, true));
+        match glob.get(j) {
+            Some(',') => j += 1,
+            Some('}') => break,
+            _ => return None,
+        }
+    }
+    *i = j + 1;
+    Some(Token::Alt(alts))
+}
+
+fn match_tokens(tokens: &[Token], path: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else { return path.is_empty() };
+    match token {
+        Token::Char(c) => path.first() == Some(c) && match_tokens(rest, &path[1..]),
+        Token::AnyChar => path.first().is_some_and(|c| *c != '/') && match_tokens(rest, &path[1..]),
+        Token::Star => {
+            let stop = path.iter().position(|c| *c == '/').unwrap_or(path.len());
+            (0..=stop).any(|n| match_tokens(rest, &path[n..]))
+        }
+        Token::DoubleStar => {
+            // `**/` also matches no directory at all
+            let no_dir = matches!(rest.first(), Some(Token::Char('/'))) && match_tokens(&rest[1..], path);
+            no_dir || (0..=path.len()).any(|n| match_tokens(rest, &path[n..]))
+        }
+        Token::Class(negate, ranges) => match path.first() {
+            Some(c) if *c != '/' => {
+                let found = ranges.iter().any(|(lo, hi)| lo <= c && c <= hi);
+                found != *negate && match_tokens(rest, &path[1..])
+            }
+            _ => false,
+        },
+        Token::Alt(alts) => alts.iter().any(|alt| {
+            let tokens: Vec<Token> = alt.iter().chain(rest).cloned().collect();
+            match_tokens(&tokens, path)
+        }),
+        Token::Range(lo, hi) => {
+            let sign = usize::from(path.first() == Some(&'-'));
+            let digits = path[sign..].iter().take_while(|c| c.is_ascii_digit()).count();
+            (1..=digits).any(|n| {
+                let number: String = path[..sign + n].iter().collect();
+                number.parse().is_ok_and(|v: i64| *lo <= v && v <= *hi) && match_tokens(rest, &path[sign + n..])
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_match("*.py", "setup.py"));
+        assert!(!glob_match("*.py", "lib/setup.py"));
+        assert!(glob_match("**/*.py", "lib/setup.py"));
+        assert!(glob_match("**/Makefile", "Makefile"));
+        assert!(glob_match("*.{c,h}", "main.h"));
+        assert!(!glob_match("*.{c,h}", "main.cc"));
+        assert!(glob_match("file[0-9].txt", "file3.txt"));
+        assert!(!glob_match("file[!0-9].txt", "file3.txt"));
+        assert!(glob_match("log{1..10}", "log10"));
+        assert!(!glob_match("log{1..10}", "log11"));
+        assert!(glob_match("{src,lib}/**.{rs,toml}", "src/a/b.rs"));
+        assert!(glob_match("a\\*b", "a*b"));
+        assert!(!glob_match("a\\*b", "axb"));
+    }
+
+    #[test]
+    fn test_properties() {
+        let config = EditorConfig::parse("root = true\n\
+            ; all files\n\
+            [*]\n\
+            indent_style = space\n\
+            indent_size = 4\n\
+            [Makefile]\n\
+            indent_style = Tab\n\
+            indent_size = unset\n\
+            [/docs/*.md]\n\
+            trim_trailing_whitespace = false\n");
+        assert!(config.root);
+
+        let mut props = Vec::new();
+        config.apply("sub/Makefile", &mut props);
+        assert_eq!(vec![("indent_style".to_string(), "tab".to_string())], props);
+        assert_eq!(vec![("expand_tabs", Value::Bool(false))], settings(&props));
+
+        let mut props = Vec::new();
+        config.apply("docs/a.md", &mut props);
+        assert_eq!(vec![
+            ("expand_tabs", Value::Bool(true)),
+            ("indent_width", Value::Int(4)),
+            ("tab_width", Value::Int(4)),
+            ("trim_trailing_whitespace", Value::Bool(false)),
+        ], settings(&props));
+
+        let mut props = Vec::new();
+        config.apply("sub/docs/a.md", &mut props);
+        assert_eq!(2, props.len());
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+use crate::key::{Event, Input};
+
+/// Write end of the pipe signal handlers report to, -1 before there is one.
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// What woke the event loop up.
+#[derive(PartialEq, Debug)]
+pub enum Wake<T> {
+    /// Terminal input.
+    Input(Event),
+    /// A signal watched with `watch_signal`.
+    Signal(i32),
+    /// A timer set with `set_timer` ran out.
+    Timer(T),
+    /// A source added with `add_source` has something to read.
+    Readable(T),
+    /// The terminal is gone.
+    Hangup,
+}
+
+/// Waits on terminal input, signals, timers and other file descriptors at
+/// once, so that nothing has to block on the keyboard. Timers and sources
+/// are told apart by the tokens they were registered with.
+pub struct EventLoop<T> {
+    pub input: Input,
+    timers: Vec<(Instant, T)>,
+    sources: Vec<(RawFd, T)>,
+    /* read and write end of the signal pipe */
+    signals: RawFd,
+    signals_write: RawFd,
+    ready: VecDeque<Wake<T>>,
+}
+
+impl<T: Copy + PartialEq> EventLoop<T> {
+    pub fn new(input: Input) -> io::Result<Self> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        for fd in fds {
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFL, libc::fcntl(fd, libc::F_GETFL) | libc::O_NONBLOCK);
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        // signals go to the newest loop, another one keeps its pipe until it is dropped
+        SIGNAL_PIPE.store(fds[1], Ordering::SeqCst);
+        Ok(EventLoop {
+            input,
+            timers: Vec::new(),
+            sources: Vec::new(),
+            signals: fds[0],
+            signals_write: fds[1],
+            ready: VecDeque::new(),
+        })
+    }
+
+    /// Report `signal` as `Wake::Signal` instead of its default action.
+    pub fn watch_signal(&mut self, signal: i32) -> io::Result<()> {
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        unsafe { libc::sigemptyset(&mut action.sa_mask) };
+        if unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Wake up with `Wake::Timer(token)` `after` from now, replacing the
+    /// token's timer if it had one.
+    pub fn set_timer(&mut self, token: T, after: Duration) {
+        self.cancel_timer(token);
+        self.timers.push((Instant::now() + after, token));
+    }
+
+    pub fn cancel_timer(&mut self, token: T) {
+        self.timers.retain(|(_, t)| *t != token);
+    }
+
+    pub fn has_timer(&self, token: T) -> bool {
+        self.timers.iter().any(|(_, t)| *t == token)
+    }
+
+    /// Wake up with `Wake::Readable(token)` whenever `fd` can be read.
+    pub fn add_source(&mut self, fd: RawFd, token: T) {
+        self.sources.push((fd, token));
+    }
+
+    pub fn remove_source(&mut self, fd: RawFd) {
+        let Some(i) = self.sources.iter().position(|(f, _)| *f == fd) else { return };
+        let (_, token) = self.sources.remove(i);
+        self.ready.retain(|w| !matches!(w, Wake::Readable(t) if *t == token));
+    }
+
+    /// Wait for the next thing to happen.
+    pub fn next(&mut self) -> Wake<T> {
+        loop {
+            if let Some(event) = self.input.next_event() {
+                return Wake::Input(event);
+            }
+            if let Some(wake) = self.ready.pop_front() {
+                return wake;
+            }
+            let now = Instant::now();
+            let due = self.timers.iter().enumerate().filter(|(_, (at, _))| *at <= now).min_by_key(|(_, (at, _))| *at);
+            if let Some((i, _)) = due {
+                return Wake::Timer(self.timers.remove(i).1);
+            }
+
+            let deadline = self.timers.iter().map(|(at, _)| *at).chain(self.input.deadline()).min();
+            let timeout = match deadline {
+                // round up, so as not to wake just before the deadline
+                Some(at) => at.saturating_duration_since(now).as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32,
+                None => -1,
+            };
+            let mut fds: Vec<libc::pollfd> = [self.input.fd(), self.signals].iter().chain(self.sources.iter().map(|(fd, _)| fd))
+                .map(|&fd| libc::pollfd { fd, events: libc::POLLIN, revents: 0 })
+                .collect();
+            if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } <= 0 {
+                // timed out, or interrupted by a signal now waiting in the pipe
+                continue;
+            }
+
+            if fds[1].revents != 0 {
+                let mut buf = [0_u8; 64];
+                loop {
+                    let n = unsafe { libc::read(self.signals, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+                    if n <= 0 {
+                        break;
+                    }
+                    self.ready.extend(buf[..n as usize].iter().map(|&s| Wake::Signal(s as i32)));
+                }
+            }
+            for (pollfd, (_, token)) in fds[2..].iter().zip(&self.sources) {
+                if pollfd.revents != 0 {
+                    self.ready.push_back(Wake::Readable(*token));
+                }
+            }
+            if fds[0].revents != 0 {
+                match self.input.read() {
+                    Ok(true) => {}
+                    Err(e) if matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock) => {}
+                    Ok(false) | Err(_) => return Wake::Hangup,
+                }
+            }
+        }
+    }
+}
+
+impl<T> Drop for EventLoop<T> {
+    fn drop(&mut self) {
+        // the handler stops writing to the pipe before it is closed, unless it is another loop's now
+        let _ = SIGNAL_PIPE.compare_exchange(self.signals_write, -1, Ordering::SeqCst, Ordering::SeqCst);
+        unsafe {
+            libc::close(self.signals_write);
+            libc::close(self.signals);
+        }
+    }
+}
+
+/* only async-signal-safe calls in here: pass the signal on through the pipe */
+extern "C" fn on_signal(signal: libc::c_int) {
+    let fd = SIGNAL_PIPE.load(Ordering::SeqCst);
+    if fd >= 0 {
+        let byte = signal as u8;
+        unsafe { libc::write(fd, &byte as *const u8 as *const libc::c_void, 1) };
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{self, Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+use crate::config::LineEnding;
+use crate::util;
+
+pub const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// How a file was written, so saving it changes nothing but the edits.
+#[derive(PartialEq, Debug)]
+pub struct TextFormat {
+    /// The most common line break, `None` when there is none.
+    pub line_ending: Option<LineEnding>,
+    /// More than one kind of line break was found.
+    pub mixed: bool,
+    /// `None` for empty text.
+    pub final_newline: Option<bool>,
+    pub bom: bool,
+}
+
+/// What a file looked like when it was read or written, to notice other
+/// programs changing it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FileStamp {
+    pub modified: SystemTime,
+    pub size: u64,
+    pub hash: u64,
+}
+
+impl FileStamp {
+    pub fn new(meta: &Metadata, content: &[u8]) -> FileStamp {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        FileStamp { modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), size: meta.len(), hash: hasher.finish() }
+    }
+
+    /// How the file at `path` compares with this stamp.
+    pub fn check(&self, path: &Path) -> io::Result<OnDisk> {
+        let meta = fs::metadata(path)?;
+        if meta.modified().ok() == Some(self.modified) && meta.len() == self.size {
+            return Ok(OnDisk::Same);
+        }
+        let content = fs::read(path)?;
+        let stamp = FileStamp::new(&meta, &content);
+        if stamp.hash == self.hash {
+            return Ok(OnDisk::Touched(stamp));
+        }
+        Ok(OnDisk::Changed(stamp, content))
+    }
+}
+
+pub enum OnDisk {
+    Same,
+    /// Written again with the same contents.
+    Touched(FileStamp),
+    /// Changed by someone else, with its stamp and contents now.
+    Changed(FileStamp, Vec<u8>),
+}
+
+/// Split file contents into lines, noting how they were written.
+pub fn split_text(text: &[u8]) -> (TextFormat, Vec<&[u8]>) {
+    let bom = text.starts_with(BOM);
+    let text = if bom { &text[BOM.len()..] } else { text };
+
+    let mut counts = [(LineEnding::Lf, 0), (LineEnding::CrLf, 0), (LineEnding::Cr, 0)];
+    let mut lines = Vec::new();
+    let (mut start, mut i) = (0, 0);
+    while i < text.len() {
+        let ending = match text[i] {
+            b'\n' => Some(0),
+            b'\r' if text.get(i + 1) == Some(&b'\n') => Some(1),
+            b'\r' => Some(2),
+            _ => None,
+        };
+        if let Some(ending) = ending {
+            counts[ending].1 += 1;
+            lines.push(&text[start..i]);
+            i += counts[ending].0.as_bytes().len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    let final_newline = if text.is_empty() { None } else { Some(start == text.len()) };
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+
+    let used = counts.iter().filter(|(_, n)| *n > 0).count();
+    let line_ending = counts.iter().filter(|(_, n)| *n > 0).max_by_key(|(_, n)| *n).map(|(e, _)| *e);
+    (TextFormat { line_ending, mixed: used > 1, final_newline, bom }, lines)
+}
+
+/// Replace the file at `path` with `content` so that a crash or a full disk
+/// leaves either the old or the new text, never a mix: write a temp file
+/// next to it, sync it and rename it over the original. The original's
+/// permissions and, where allowed, owner are kept. A symlink is followed
+/// and its target replaced; a file with several hard links is written in
+/// place instead, as renaming would split it from the other names.
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let path = match fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e),
+    };
+    let meta = match fs::metadata(&path) {
+        Ok(meta) => Some(meta),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    if meta.as_ref().is_some_and(|m| m.nlink() > 1) {
+        return write_in_place(&path, content);
+    }
+
+    let (tmp_path, mut tmp) = create_temp(&path)?;
+    let written = (|| {
+        if let Some(meta) = &meta {
+            tmp.set_permissions(meta.permissions())?;
+            // only root may give files away, keep our own uid then
+            unsafe { libc::fchown(tmp.as_raw_fd(), meta.uid(), meta.gid()) };
+        }
+        tmp.write_all(content)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &path)
+    })();
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    // make the rename itself durable
+    if let Ok(dir) = File::open(parent_dir(&path)) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Copy the file at `path` before it is overwritten: to `file~` beside
+/// it, or with `dir` to a copy named after its absolute path and the time.
+/// Returns where the copy went, nothing when there is no file yet.
+pub fn backup(path: &Path, dir: Option<&Path>) -> io::Result<Option<PathBuf>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let backup = match dir {
+        None => {
+            let mut name = path.as_os_str().to_owned();
+            name.push("~");
+            PathBuf::from(name)
+        }
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            let name = path::absolute(path)?.to_string_lossy().replace('/', "%");
+            dir.join(format!("{}.{}", name, util::local_timestamp()))
+        }
+    };
+    fs::copy(path, &backup)?;
+    Ok(Some(backup))
+}
+
+fn write_in_place(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+/* a new file beside `path`, hidden and named after it */
+fn create_temp(path: &Path) -> io::Result<(PathBuf, File)> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    for n in 0.. {
+        let tmp_path = parent_dir(path).join(format!(".{}.{}.{}.tmp", name, process::id(), n));
+        match OpenOptions::new().write(true).create_new(true).mode(0o666).open(&tmp_path) {
+            Ok(file) => return Ok((tmp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use crate::util::TempDir;
+
+    #[test]
+    fn test_split_text() {
+        let (format, lines) = split_text(b"\xef\xbb\xbfa\r\nb\r\nc");
+        assert_eq!(TextFormat { line_ending: Some(LineEnding::CrLf), mixed: false, final_newline: Some(false), bom: true }, format);
+        assert_eq!(vec![&b"a"[..], b"b", b"c"], lines);
+
+        let (format, lines) = split_text(b"a\nb\r\nc\n\n");
+        assert_eq!(TextFormat { line_ending: Some(LineEnding::Lf), mixed: true, final_newline: Some(true), bom: false }, format);
+        assert_eq!(vec![&b"a"[..], b"b", b"c", b""], lines);
+
+        let (format, lines) = split_text(b"");
+        assert_eq!(TextFormat { line_ending: None, mixed: false, final_newline: None, bom: false }, format);
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let temp = TempDir::new();
+        let dir = &temp.path;
+        let path = dir.join("a.txt");
+
+        write_atomic(&path, b"one\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink(&path, dir.join("link")).unwrap();
+        write_atomic(&dir.join("link"), b"two\n").unwrap();
+
+        assert_eq!(b"two\n".to_vec(), fs::read(&path).unwrap());
+        assert_eq!(0o640, fs::metadata(&path).unwrap().permissions().mode() & 0o777);
+        assert!(fs::symlink_metadata(dir.join("link")).unwrap().file_type().is_symlink());
+        // no temp file left behind
+        assert_eq!(2, fs::read_dir(dir).unwrap().count());
+    }
+}
@@ -11,16 +11,48 @@ pub enum Highlight {
     Match,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Theme {
+    Default,
+    /// Attributes only, for terminals without colors.
+    Mono,
+}
+
+const THEMES: &[(Theme, &str)] = &[(Theme::Default, "default"), (Theme::Mono, "mono")];
+
+impl Theme {
+    pub fn name(self) -> &'static str {
+        THEMES.iter().find(|(t, _)| *t == self).map(|(_, n)| *n).unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<Theme> {
+        THEMES.iter().find(|(_, n)| *n == name).map(|(t, _)| *t)
+    }
+
+    pub fn names() -> String {
+        THEMES.iter().map(|(_, n)| *n).collect::<Vec<_>>().join(", ")
+    }
+}
+
 impl Highlight {
-    pub fn to_color(&self) -> &'static str {
-        match self {
-            Highlight::Number => "\x1b[31m",
-            Highlight::Comment | Highlight::MComment => "\x1b[36m",
-            Highlight::Normal => "\x1b[39m",
-            Highlight::Match => "\x1b[34m",
-            Highlight::String => "\x1b[35m",
-            Highlight::Keyword1 => "\x1b[33m",
-            Highlight::Keyword2 => "\x1b[32m",
+    pub fn to_color(&self, theme: Theme) -> &'static str {
+        match theme {
+            Theme::Default => match self {
+                Highlight::Number => "\x1b[31m",
+                Highlight::Comment | Highlight::MComment => "\x1b[36m",
+                Highlight::Normal => "\x1b[39m",
+                Highlight::Match => "\x1b[34m",
+                Highlight::String => "\x1b[35m",
+                Highlight::Keyword1 => "\x1b[33m",
+                Highlight::Keyword2 => "\x1b[32m",
+            },
+            // every entry resets what the others set
+            Theme::Mono => match self {
+                Highlight::Comment | Highlight::MComment => "\x1b[22;24;2;3m",
+                Highlight::Keyword1 | Highlight::Keyword2 => "\x1b[23;24;1m",
+                Highlight::Match => "\x1b[22;23;4m",
+                Highlight::Normal | Highlight::String | Highlight::Number => "\x1b[22;23;24m",
+            },
         }
     }
 
@@ -30,4 +62,4 @@ impl Highlight {
             des.push(h.clone());
         }
     }
-}
\ No newline at end of file
+}
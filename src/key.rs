@@ -1,107 +1,705 @@
+use std::fmt;
 use std::io;
-use std::io::Read;
-
-macro_rules! ctrl_key {
-    ($k:expr) => {($k as u8) & 0x1f};
-}
-
-#[allow(non_camel_case_types)]
-pub enum Keys {
-    BACKSPACE,
-    ARROW_LEFT,
-    ARROW_RIGHT,
-    ARROW_UP,
-    ARROW_DOWN,
-    DEL_KEY,
-    HOME_KEY,
-    END_KEY,
-    PAGE_UP,
-    PAGE_DOWN,
-    QUIT,
-    CTL_H,
-    CTL_L,
-    CTL_S,
-    CTL_F,
-    ENTER,
-    ESC,
-    NORMAL(u8),
-}
-
-impl Keys {
-    pub fn read_key() -> Keys {
-        let mut c = [0; 4];
-        let size = io::stdin().lock().read(&mut c).unwrap();
-        let r = c[0] as char;
-
-        if r == '\x1b' {
-            if size < 3 { return Keys::ESC; }
-            let r1 = c[1] as char;
-            let r2 = c[2] as char;
-
-            if r1 == '[' {
-                if r2 >= '0' && r2 <= '9' && size >= 4 {
-                    io::stdin().lock().read(&mut c).unwrap();
-                    let r3 = c[3] as char;
-                    if r3 == '~' {
-                        return match r2 {
-                            '1' => Keys::HOME_KEY,
-                            '3' => Keys::DEL_KEY,
-                            '4' => Keys::END_KEY,
-                            '5' => Keys::PAGE_UP,
-                            '6' => Keys::PAGE_DOWN,
-                            '7' => Keys::HOME_KEY,
-                            '8' => Keys::END_KEY,
-                            _ => Keys::ESC,
-                        };
-                    }
-                } else {
-                    return match r2 {
-                        'A' => Keys::ARROW_UP,
-                        'B' => Keys::ARROW_DOWN,
-                        'C' => Keys::ARROW_RIGHT,
-                        'D' => Keys::ARROW_LEFT,
-                        'H' => Keys::HOME_KEY,
-                        'F' => Keys::END_KEY,
-                        _ => Keys::ESC,
-                    };
-                }
-            } else if r1 == 'O' {
-                return match r2 {
-                    'H' => Keys::HOME_KEY,
-                    'F' => Keys::END_KEY,
-                    _ => Keys::ESC,
-                };
+use std::ops::BitOr;
+use std::os::unix::io::RawFd;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use memchr::memmem;
+
+/// Modifier keys held down while a key was pressed.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const SHIFT: Modifiers = Modifiers(1);
+    pub const ALT: Modifiers = Modifiers(2);
+    pub const CTRL: Modifiers = Modifiers(4);
+
+    pub fn contains(self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(self, other: Modifiers) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn remove(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 & !other.0)
+    }
+
+    /// xterm and kitty send modifiers as `1 + bits`, with shift, alt and ctrl in the low bits.
+    fn from_param(p: u32) -> Modifiers {
+        Modifiers((p.saturating_sub(1) & 0x7) as u8)
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, rhs: Modifiers) -> Modifiers {
+        Modifiers(self.0 | rhs.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum KeyCode {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Esc,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    F(u8),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub mods: Modifiers,
+}
+
+impl KeyEvent {
+    pub fn new(code: KeyCode, mods: Modifiers) -> Self {
+        // shifted letters arrive either as the upper case byte or as the
+        // lower case code plus SHIFT, keep a single spelling of them
+        match code {
+            KeyCode::Char(c) if mods.contains(Modifiers::SHIFT) && c.is_ascii_alphabetic() => {
+                KeyEvent { code: KeyCode::Char(c.to_ascii_uppercase()), mods: mods.remove(Modifiers::SHIFT) }
+            }
+            _ => KeyEvent { code, mods },
+        }
+    }
+
+    pub fn plain(code: KeyCode) -> Self {
+        KeyEvent { code, mods: Modifiers::NONE }
+    }
+
+    pub fn ctrl(c: char) -> Self {
+        KeyEvent { code: KeyCode::Char(c), mods: Modifiers::CTRL }
+    }
+
+    fn with(self, mods: Modifiers) -> Self {
+        KeyEvent::new(self.code, self.mods | mods)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MouseKind {
+    Press(MouseButton),
+    Release(MouseButton),
+    Drag(MouseButton),
+    ScrollUp,
+    ScrollDown,
+}
+
+/// A mouse report, with the 0 based screen cell it happened on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MouseEvent {
+    pub kind: MouseKind,
+    pub col: u32,
+    pub row: u32,
+    pub mods: Modifiers,
+}
+
+/// Everything the terminal reports through stdin.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event {
+    Key(KeyEvent),
+    /// Text pasted while bracketed paste mode is on, delivered as one block.
+    Paste(Vec<u8>),
+    Mouse(MouseEvent),
+    /// The terminal window gained (`true`) or lost keyboard focus.
+    Focus(bool),
+}
+
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+/// A paste whose end marker never came is taken as it stands after this
+/// long without input, or once it grows this large.
+const PASTE_TIMEOUT: Duration = Duration::from_secs(1);
+const PASTE_MAX: usize = 64 << 20;
+
+const KEY_NAMES: &[(KeyCode, &str)] = &[
+    (KeyCode::Enter, "Enter"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::Backspace, "Backspace"),
+    (KeyCode::Esc, "Esc"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::Delete, "Delete"),
+    (KeyCode::Char(' '), "Space"),
+];
+
+/// Written the way key maps spell keys, e.g. `Ctrl-S`, `Alt-Left` or `F5`.
+impl fmt::Display for KeyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ctrl = self.mods.contains(Modifiers::CTRL);
+        if ctrl { f.write_str("Ctrl-")?; }
+        if self.mods.contains(Modifiers::ALT) { f.write_str("Alt-")?; }
+        let shifted_letter = matches!(self.code, KeyCode::Char(c) if ctrl && c.is_ascii_uppercase());
+        if self.mods.contains(Modifiers::SHIFT) || shifted_letter { f.write_str("Shift-")?; }
+
+        if let Some((_, name)) = KEY_NAMES.iter().find(|(code, _)| *code == self.code) {
+            return f.write_str(name);
+        }
+        match self.code {
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::Char(c) if ctrl => write!(f, "{}", c.to_ascii_uppercase()),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for KeyEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mods = Modifiers::NONE;
+        let mut rest = s;
+        // a trailing '-' is the minus key itself, not a separator
+        while let Some(i) = rest.char_indices().next_back().and_then(|(last, _)| rest[..last].find('-')) {
+            mods = mods | match rest[..i].to_ascii_lowercase().as_str() {
+                "ctrl" | "c" => Modifiers::CTRL,
+                "alt" | "meta" | "a" | "m" => Modifiers::ALT,
+                "shift" | "s" => Modifiers::SHIFT,
+                _ => return Err(format!("unknown modifier '{}' in '{}'", &rest[..i], s)),
+            };
+            rest = &rest[i + 1..];
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) if mods.contains(Modifiers::CTRL) && !mods.contains(Modifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_lowercase())
             }
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match KEY_NAMES.iter().find(|(_, name)| name.eq_ignore_ascii_case(rest)) {
+                Some((code, _)) => *code,
+                None => match rest.get(1..).map(|n| n.parse::<u8>()) {
+                    Some(Ok(n)) if rest.starts_with(['F', 'f']) && (1..=20).contains(&n) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key '{}'", s)),
+                },
+            },
+        };
+        Ok(KeyEvent::new(code, mods))
+    }
+}
+
+enum Parse {
+    Key(KeyEvent, usize),
+    Paste(Vec<u8>, usize),
+    Mouse(MouseEvent, usize),
+    Focus(bool, usize),
+    Skip(usize),
+    Incomplete,
+}
+
+/// Turns the raw bytes coming from the terminal into events.
+///
+/// Bytes are buffered until they form a complete key, so an escape sequence
+/// split over several reads is still decoded as one key.
+#[derive(Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
 
-            return Keys::ESC;
+    /// Whether bytes of an unfinished key are waiting for the rest of it.
+    pub fn is_pending(&self) -> bool {
+        !self.buf.is_empty()
+    }
+
+    /// Whether the pending bytes are a paste still waiting for its end.
+    pub fn in_paste(&self) -> bool {
+        self.buf.starts_with(PASTE_START)
+    }
+
+    /// Decode the next event. With `flush` set, a pending incomplete sequence is
+    /// decoded as the keys it starts with instead of waiting for more bytes, and
+    /// a paste missing its end marker as the text that came.
+    pub fn next_event(&mut self, flush: bool) -> Option<Event> {
+        while !self.buf.is_empty() {
+            match parse_key(&self.buf, flush) {
+                Parse::Key(key, n) => {
+                    self.buf.drain(..n);
+                    return Some(Event::Key(key));
+                }
+                Parse::Paste(text, n) => {
+                    self.buf.drain(..n);
+                    return Some(Event::Paste(text));
+                }
+                Parse::Mouse(mouse, n) => {
+                    self.buf.drain(..n);
+                    return Some(Event::Mouse(mouse));
+                }
+                Parse::Focus(focused, n) => {
+                    self.buf.drain(..n);
+                    return Some(Event::Focus(focused));
+                }
+                Parse::Skip(n) => {
+                    self.buf.drain(..n);
+                }
+                Parse::Incomplete => return None,
+            }
         }
+        None
+    }
+}
+
+fn parse_key(buf: &[u8], flush: bool) -> Parse {
+    if buf.starts_with(PASTE_START) {
+        return parse_paste(buf, flush);
+    }
+    match buf[0] {
+        0x1b => parse_escape(buf, flush),
+        0x80.. => parse_utf8(buf, flush),
+        c => Parse::Key(key_from_byte(c), 1),
+    }
+}
+
+fn key_from_byte(c: u8) -> KeyEvent {
+    match c {
+        b'\r' | b'\n' => KeyEvent::plain(KeyCode::Enter),
+        b'\t' => KeyEvent::plain(KeyCode::Tab),
+        0x7f => KeyEvent::plain(KeyCode::Backspace),
+        0x1b => KeyEvent::plain(KeyCode::Esc),
+        0 => KeyEvent::ctrl(' '),
+        1..=26 => KeyEvent::ctrl((b'a' + c - 1) as char),
+        28..=31 => KeyEvent::ctrl((b'\\' + c - 28) as char),
+        _ => KeyEvent::plain(KeyCode::Char(c as char)),
+    }
+}
 
-        if r == ctrl_key!('q') as char {
-            return Keys::QUIT;
+fn key_from_codepoint(code: u32) -> Option<KeyCode> {
+    match code {
+        13 => Some(KeyCode::Enter),
+        9 => Some(KeyCode::Tab),
+        27 => Some(KeyCode::Esc),
+        8 | 127 => Some(KeyCode::Backspace),
+        // private use area, kitty's keypad and media keys
+        0xe000..=0xf8ff => None,
+        _ => char::from_u32(code).map(KeyCode::Char),
+    }
+}
+
+fn parse_utf8(buf: &[u8], flush: bool) -> Parse {
+    let len = match buf[0] {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Parse::Skip(1),
+    };
+    if buf.len() < len {
+        return if flush { Parse::Skip(buf.len()) } else { Parse::Incomplete };
+    }
+    match std::str::from_utf8(&buf[..len]) {
+        Ok(s) => Parse::Key(KeyEvent::plain(KeyCode::Char(s.chars().next().unwrap())), len),
+        Err(_) => Parse::Skip(1),
+    }
+}
+
+/// Pasted text is taken verbatim up to the end marker, however long it takes
+/// to arrive.
+fn parse_paste(buf: &[u8], flush: bool) -> Parse {
+    let text = &buf[PASTE_START.len()..];
+    match memmem::find(text, PASTE_END) {
+        Some(end) => Parse::Paste(text[..end].to_vec(), PASTE_START.len() + end + PASTE_END.len()),
+        // the end marker got lost, don't swallow all input after it
+        None if flush || text.len() >= PASTE_MAX => Parse::Paste(text.to_vec(), buf.len()),
+        None => Parse::Incomplete,
+    }
+}
+
+fn parse_escape(buf: &[u8], flush: bool) -> Parse {
+    if buf.len() == 1 {
+        return if flush { Parse::Key(KeyEvent::plain(KeyCode::Esc), 1) } else { Parse::Incomplete };
+    }
+
+    let alt = |parse: Parse| match parse {
+        Parse::Key(key, n) => Parse::Key(key.with(Modifiers::ALT), n + 1),
+        Parse::Paste(_, n) | Parse::Mouse(_, n) | Parse::Focus(_, n) | Parse::Skip(n) => Parse::Skip(n + 1),
+        Parse::Incomplete => Parse::Incomplete,
+    };
+
+    match buf[1] {
+        b'[' => match parse_csi(&buf[2..]) {
+            Parse::Incomplete if flush && buf.len() == 2 => Parse::Key(KeyEvent::new(KeyCode::Char('['), Modifiers::ALT), 2),
+            Parse::Incomplete if flush => Parse::Skip(buf.len()),
+            Parse::Key(key, n) => Parse::Key(key, n + 2),
+            Parse::Mouse(mouse, n) => Parse::Mouse(mouse, n + 2),
+            Parse::Focus(focused, n) => Parse::Focus(focused, n + 2),
+            Parse::Paste(_, n) | Parse::Skip(n) => Parse::Skip(n + 2),
+            Parse::Incomplete => Parse::Incomplete,
+        },
+        b'O' => {
+            if buf.len() == 2 {
+                return if flush { Parse::Key(KeyEvent::new(KeyCode::Char('O'), Modifiers::ALT), 2) } else { Parse::Incomplete };
+            }
+            match ss3_key(buf[2]) {
+                Some(code) => Parse::Key(KeyEvent::plain(code), 3),
+                None => Parse::Skip(3),
+            }
         }
+        // some terminals prefix a whole escape sequence with ESC for alt
+        0x1b if buf.len() == 2 && !flush => Parse::Incomplete,
+        0x1b if buf.len() == 2 => Parse::Key(KeyEvent::plain(KeyCode::Esc), 1),
+        0x80.. => alt(parse_utf8(&buf[1..], flush)),
+        0x1b => alt(parse_escape(&buf[1..], flush)),
+        c => Parse::Key(key_from_byte(c).with(Modifiers::ALT), 2),
+    }
+}
+
+fn ss3_key(c: u8) -> Option<KeyCode> {
+    match c {
+        b'A' => Some(KeyCode::Up),
+        b'B' => Some(KeyCode::Down),
+        b'C' => Some(KeyCode::Right),
+        b'D' => Some(KeyCode::Left),
+        b'H' => Some(KeyCode::Home),
+        b'F' => Some(KeyCode::End),
+        b'P'..=b'S' => Some(KeyCode::F(c - b'P' + 1)),
+        _ => None,
+    }
+}
 
-        if r == ctrl_key!('h') as char {
-            return Keys::CTL_H;
+/// Control sequence after the `ESC [`. Returns the number of bytes used
+/// from `buf`, not counting the introducer.
+fn parse_csi(buf: &[u8]) -> Parse {
+    // parameter bytes, then intermediate bytes, then a single final byte
+    let mut i = 0;
+    while i < buf.len() && (0x20..=0x3f).contains(&buf[i]) {
+        i += 1;
+    }
+    if i == buf.len() {
+        return Parse::Incomplete;
+    }
+    let fin = buf[i];
+    if !(0x40..=0x7e).contains(&fin) {
+        return Parse::Skip(i);
+    }
+    let len = i + 1;
+
+    if buf[0] == b'<' && (fin == b'M' || fin == b'm') {
+        return match parse_sgr_mouse(&buf[1..i], fin == b'm') {
+            Some(mouse) => Parse::Mouse(mouse, len),
+            None => Parse::Skip(len),
+        };
+    }
+
+    let params = match std::str::from_utf8(&buf[..i]) {
+        Ok(p) if p.bytes().all(|c| c.is_ascii_digit() || c == b';' || c == b':') => p,
+        _ => return Parse::Skip(len),
+    };
+    // each parameter may carry sub parameters separated by ':', only the first one matters here
+    let param = |n: usize, default: u32| -> u32 {
+        params.split(';').nth(n)
+            .and_then(|p| p.split(':').next())
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(default)
+    };
+    let mods = Modifiers::from_param(param(1, 1));
+
+    let code = match fin {
+        b'A' | b'B' | b'C' | b'D' | b'H' | b'F' | b'P' | b'Q' | b'R' | b'S' => ss3_key(fin),
+        b'Z' => return Parse::Key(KeyEvent::new(KeyCode::Tab, Modifiers::SHIFT), len),
+        // focus reporting
+        b'I' | b'O' if i == 0 => return Parse::Focus(fin == b'I', len),
+        b'~' => match param(0, 0) {
+            1 | 7 => Some(KeyCode::Home),
+            2 => Some(KeyCode::Insert),
+            3 => Some(KeyCode::Delete),
+            4 | 8 => Some(KeyCode::End),
+            5 => Some(KeyCode::PageUp),
+            6 => Some(KeyCode::PageDown),
+            n @ 11..=15 => Some(KeyCode::F((n - 10) as u8)),
+            n @ 17..=21 => Some(KeyCode::F((n - 11) as u8)),
+            n @ 23..=26 => Some(KeyCode::F((n - 12) as u8)),
+            n @ 28..=29 => Some(KeyCode::F((n - 13) as u8)),
+            n @ 31..=34 => Some(KeyCode::F((n - 14) as u8)),
+            // xterm modifyOtherKeys: CSI 27 ; mods ; code ~
+            27 => key_from_codepoint(param(2, 0)),
+            _ => None,
+        },
+        // kitty keyboard protocol: CSI code ; mods u
+        b'u' => key_from_codepoint(param(0, 0)),
+        _ => None,
+    };
+
+    match code {
+        Some(code) => Parse::Key(KeyEvent::new(code, mods), len),
+        None => Parse::Skip(len),
+    }
+}
+
+/// SGR mouse report `CSI < button ; col ; row M`, with `m` for a release.
+fn parse_sgr_mouse(params: &[u8], release: bool) -> Option<MouseEvent> {
+    let params = std::str::from_utf8(params).ok()?;
+    let mut it = params.split(';').map(|p| p.parse::<u32>());
+    let (b, col, row) = (it.next()?.ok()?, it.next()?.ok()?, it.next()?.ok()?);
+
+    let mut mods = Modifiers::NONE;
+    if b & 4 != 0 { mods = mods | Modifiers::SHIFT; }
+    if b & 8 != 0 { mods = mods | Modifiers::ALT; }
+    if b & 16 != 0 { mods = mods | Modifiers::CTRL; }
+
+    let button = match b & 3 {
+        0 => MouseButton::Left,
+        1 => MouseButton::Middle,
+        2 => MouseButton::Right,
+        _ => return None,
+    };
+    let kind = if b & 64 != 0 {
+        match b & 3 {
+            0 => MouseKind::ScrollUp,
+            1 => MouseKind::ScrollDown,
+            _ => return None,
         }
+    } else if b & 32 != 0 {
+        MouseKind::Drag(button)
+    } else if release {
+        MouseKind::Release(button)
+    } else {
+        MouseKind::Press(button)
+    };
+
+    Some(MouseEvent { kind, col: col.saturating_sub(1), row: row.saturating_sub(1), mods })
+}
+
+/// Decodes the terminal input read by the event loop.
+///
+/// A lone escape byte is ambiguous: it is either the Escape key or the start
+/// of a sequence (or an Alt prefix) whose remaining bytes are still on the
+/// way. Pending bytes are given `esc_timeout` to complete before they are
+/// decoded as they stand.
+pub struct Input {
+    fd: RawFd,
+    decoder: Decoder,
+    esc_timeout: Duration,
+    /// When pending bytes stop waiting for the rest of their sequence.
+    esc_deadline: Option<Instant>,
+}
+
+impl Input {
+    pub fn new(esc_timeout: Duration) -> Self {
+        Input::from_fd(libc::STDIN_FILENO, esc_timeout)
+    }
 
-        if r == ctrl_key!('l') as char {
-            return Keys::CTL_L;
+    /// Input read from `fd` instead of stdin.
+    pub fn from_fd(fd: RawFd, esc_timeout: Duration) -> Self {
+        Input { fd, decoder: Decoder::default(), esc_timeout, esc_deadline: None }
+    }
+
+    /// The descriptor to poll for input.
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    pub fn set_esc_timeout(&mut self, esc_timeout: Duration) {
+        self.esc_timeout = esc_timeout;
+    }
+
+    /// The next complete event among the bytes read so far, or the pending
+    /// bytes as they stand once their deadline passed.
+    pub fn next_event(&mut self) -> Option<Event> {
+        if let Some(event) = self.decoder.next_event(false) {
+            return Some(event);
         }
-        if r == ctrl_key!('s') as char {
-            return Keys::CTL_S;
+        match self.esc_deadline {
+            Some(deadline) if deadline <= Instant::now() => {
+                let event = self.decoder.next_event(true);
+                self.set_deadline();
+                event
+            }
+            _ => None,
         }
-        if r == ctrl_key!('f') as char {
-            return Keys::CTL_F;
+    }
+
+    /// When the pending bytes are due to be decoded, if there are any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.esc_deadline
+    }
+
+    /// Read all the input there is, once it polled readable. `Ok(false)` at
+    /// end of file. The descriptor is read directly: bytes std's buffered
+    /// stdin held on to would not wake the next poll.
+    pub fn read(&mut self) -> io::Result<bool> {
+        let mut buf = [0_u8; 4096];
+        let mut read = false;
+        loop {
+            let n = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+            if n < 0 && !read {
+                return Err(io::Error::last_os_error());
+            }
+            if n <= 0 {
+                // nothing more for now, or end of file, which the next read reports
+                break;
+            }
+            self.decoder.feed(&buf[..n as usize]);
+            read = true;
+            // a short read took all there was, otherwise look whether more is waiting without blocking
+            if (n as usize) < buf.len() || !self.has_more() {
+                break;
+            }
         }
+        self.set_deadline();
+        Ok(read)
+    }
+
+    fn has_more(&self) -> bool {
+        let mut pollfd = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+        unsafe { libc::poll(&mut pollfd, 1, 0) > 0 }
+    }
+
+    fn set_deadline(&mut self) {
+        // a paste gets longer than a key sequence, it may come in slowly over a remote link
+        let timeout = if self.decoder.in_paste() { PASTE_TIMEOUT.max(self.esc_timeout) } else { self.esc_timeout };
+        self.esc_deadline = if self.decoder.is_pending() { Some(Instant::now() + timeout) } else { None };
+    }
+}
 
-        if r == '\r' {
-            return Keys::ENTER;
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> Vec<KeyEvent> {
+        let mut d = Decoder::default();
+        d.feed(bytes);
+        let mut keys = Vec::new();
+        while let Some(Event::Key(k)) = d.next_event(true) {
+            keys.push(k);
         }
+        keys
+    }
+
+    #[test]
+    fn test_legacy_keys() {
+        assert_eq!(vec![KeyEvent::ctrl('q'), KeyEvent::plain(KeyCode::Enter), KeyEvent::plain(KeyCode::Char('a'))],
+                   decode(b"\x11\ra"));
+        assert_eq!(vec![KeyEvent::new(KeyCode::Tab, Modifiers::SHIFT)], decode(b"\x1b[Z"));
+        assert_eq!(vec![KeyEvent::new(KeyCode::Char('f'), Modifiers::ALT)], decode(b"\x1bf"));
+        assert_eq!(vec![KeyEvent::plain(KeyCode::F(12)), KeyEvent::plain(KeyCode::F(1))], decode(b"\x1b[24~\x1bOP"));
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert_eq!(vec![KeyEvent::new(KeyCode::Right, Modifiers::CTRL)], decode(b"\x1b[1;5C"));
+        assert_eq!(vec![KeyEvent::new(KeyCode::Up, Modifiers::SHIFT)], decode(b"\x1b[1;2A"));
+        assert_eq!(vec![KeyEvent::new(KeyCode::Delete, Modifiers::CTRL | Modifiers::ALT)], decode(b"\x1b[3;7~"));
+        // modifyOtherKeys and kitty
+        assert_eq!(vec![KeyEvent::ctrl('s')], decode(b"\x1b[27;5;115~"));
+        assert_eq!(vec![KeyEvent::plain(KeyCode::Esc), KeyEvent::new(KeyCode::Char('A'), Modifiers::CTRL)],
+                   decode(b"\x1b[27u\x1b[97;6u"));
+    }
+
+    #[test]
+    fn test_split_sequence() {
+        let mut d = Decoder::default();
+        d.feed(b"\x1b[1;");
+        assert_eq!(None, d.next_event(false));
+        d.feed(b"5D");
+        assert_eq!(Some(Event::Key(KeyEvent::new(KeyCode::Left, Modifiers::CTRL))), d.next_event(false));
+        assert!(!d.is_pending());
 
-        if c[0] == 127 {
-            return Keys::BACKSPACE;
+        d.feed(b"\x1b");
+        assert_eq!(None, d.next_event(false));
+        assert!(d.is_pending());
+        assert_eq!(Some(Event::Key(KeyEvent::plain(KeyCode::Esc))), d.next_event(true));
+    }
+
+    #[test]
+    fn test_key_names() {
+        for name in ["Ctrl-S", "Alt-Left", "Ctrl-Shift-Z", "F5", "Shift-Tab", "Alt-x", "Ctrl-Space", "Ctrl--"] {
+            assert_eq!(name, name.parse::<KeyEvent>().unwrap().to_string());
         }
+        assert_eq!(KeyEvent::ctrl('x'), "C-x".parse().unwrap());
+        assert_eq!(KeyEvent::plain(KeyCode::Char('é')), "é".parse().unwrap());
+        assert_eq!(KeyEvent::new(KeyCode::Char('ö'), Modifiers::CTRL), "Ctrl-ö".parse().unwrap());
+        assert!("ö-x".parse::<KeyEvent>().is_err());
+        assert!("Hyper-x".parse::<KeyEvent>().is_err());
+        assert!("Foo".parse::<KeyEvent>().is_err());
+    }
 
-        return Keys::NORMAL(c[0]);
+    #[test]
+    fn test_mouse() {
+        let mut d = Decoder::default();
+        d.feed(b"\x1b[<0;5;3M\x1b[<32;6;3M\x1b[<0;6;3m\x1b[<65;1;1M");
+        let kinds: Vec<(MouseKind, u32, u32)> = std::iter::from_fn(|| match d.next_event(false) {
+            Some(Event::Mouse(m)) => Some((m.kind, m.col, m.row)),
+            _ => None,
+        }).collect();
+        assert_eq!(vec![(MouseKind::Press(MouseButton::Left), 4, 2), (MouseKind::Drag(MouseButton::Left), 5, 2),
+                        (MouseKind::Release(MouseButton::Left), 5, 2), (MouseKind::ScrollDown, 0, 0)], kinds);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_paste() {
+        let mut d = Decoder::default();
+        d.feed(b"\x1b[200~a\tb\r\x1b");
+        assert_eq!(None, d.next_event(false));
+        d.feed(b"[Ac\x1b[201~x");
+        assert_eq!(Some(Event::Paste(b"a\tb\r\x1b[Ac".to_vec())), d.next_event(false));
+        assert_eq!(Some(Event::Key(KeyEvent::plain(KeyCode::Char('x')))), d.next_event(false));
+
+        d.feed(b"\x1b[I\x1b[O");
+        assert_eq!(Some(Event::Focus(true)), d.next_event(false));
+        assert_eq!(Some(Event::Focus(false)), d.next_event(false));
+
+        // no end marker
+        d.feed(b"\x1b[200~lost");
+        assert_eq!(None, d.next_event(false));
+        assert_eq!(Some(Event::Paste(b"lost".to_vec())), d.next_event(true));
+        d.feed(b"y");
+        assert_eq!(Some(Event::Key(KeyEvent::plain(KeyCode::Char('y')))), d.next_event(false));
+    }
+
+    #[test]
+    fn test_long_paste() {
+        let mut fds = [0; 2];
+        assert_eq!(0, unsafe { libc::pipe(fds.as_mut_ptr()) });
+        let text = vec![b'x'; 10000];
+        let mut bytes = PASTE_START.to_vec();
+        bytes.extend_from_slice(&text);
+        bytes.extend_from_slice(PASTE_END);
+        bytes.push(b'y');
+        assert_eq!(bytes.len() as isize, unsafe { libc::write(fds[1], bytes.as_ptr() as *const libc::c_void, bytes.len()) });
+
+        // all of it in one go, the way the event loop reads once the descriptor polled readable
+        let mut input = Input::from_fd(fds[0], Duration::from_millis(50));
+        assert!(input.read().unwrap());
+        assert_eq!(Some(Event::Paste(text)), input.next_event());
+        assert_eq!(Some(Event::Key(KeyEvent::plain(KeyCode::Char('y')))), input.next_event());
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::key::{KeyCode, KeyEvent, Modifiers};
+
+/// Editor commands that keys can be bound to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    Quit,
+    Save,
+    Find,
+    Redraw,
+    NewLine,
+    DeleteBackward,
+    DeleteForward,
+    InsertTab,
+    Indent,
+    Outdent,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    WordLeft,
+    WordRight,
+    LineStart,
+    LineEnd,
+    MatchBracket,
+    PageUp,
+    PageDown,
+    Execute,
+    Suspend,
+    Visual,
+    VisualLine,
+    VisualBlock,
+    BlockInsert,
+    BlockAppend,
+    UpperCase,
+    LowerCase,
+    Cut,
+    Copy,
+    Paste,
+    Register,
+    AddCursorNext,
+    AddCursorAbove,
+    AddCursorBelow,
+    CursorsAtMatches,
+}
+
+/// Every command, with the name key map files use for it.
+const COMMANDS: &[(Command, &str)] = &[
+    (Command::Quit, "quit"),
+    (Command::Save, "save"),
+    (Command::Find, "find"),
+    (Command::Redraw, "redraw"),
+    (Command::NewLine, "newline"),
+    (Command::DeleteBackward, "delete-backward"),
+    (Command::DeleteForward, "delete-forward"),
+    (Command::InsertTab, "insert-tab"),
+    (Command::Indent, "indent"),
+    (Command::Outdent, "outdent"),
+    (Command::MoveLeft, "move-left"),
+    (Command::MoveRight, "move-right"),
+    (Command::MoveUp, "move-up"),
+    (Command::MoveDown, "move-down"),
+    (Command::WordLeft, "word-left"),
+    (Command::WordRight, "word-right"),
+    (Command::LineStart, "line-start"),
+    (Command::LineEnd, "line-end"),
+    (Command::MatchBracket, "match-bracket"),
+    (Command::PageUp, "page-up"),
+    (Command::PageDown, "page-down"),
+    (Command::Execute, "command"),
+    (Command::Suspend, "suspend"),
+    (Command::Visual, "visual"),
+    (Command::VisualLine, "visual-line"),
+    (Command::VisualBlock, "visual-block"),
+    (Command::BlockInsert, "block-insert"),
+    (Command::BlockAppend, "block-append"),
+    (Command::UpperCase, "upper-case"),
+    (Command::LowerCase, "lower-case"),
+    (Command::Cut, "cut"),
+    (Command::Copy, "copy"),
+    (Command::Paste, "paste"),
+    (Command::Register, "register"),
+    (Command::AddCursorNext, "add-cursor-next"),
+    (Command::AddCursorAbove, "add-cursor-above"),
+    (Command::AddCursorBelow, "add-cursor-below"),
+    (Command::CursorsAtMatches, "cursors-at-matches"),
+];
+
+const DEFAULT_BINDINGS: &[(&str, Command)] = &[
+    ("Ctrl-Q", Command::Quit),
+    ("Ctrl-S", Command::Save),
+    ("Ctrl-F", Command::Find),
+    ("Ctrl-L", Command::Redraw),
+    ("Enter", Command::NewLine),
+    ("Backspace", Command::DeleteBackward),
+    ("Ctrl-H", Command::DeleteBackward),
+    ("Delete", Command::DeleteForward),
+    ("Tab", Command::InsertTab),
+    ("Shift-Tab", Command::Outdent),
+    ("Alt-.", Command::Indent),
+    ("Alt-,", Command::Outdent),
+    ("Left", Command::MoveLeft),
+    ("Right", Command::MoveRight),
+    ("Up", Command::MoveUp),
+    ("Down", Command::MoveDown),
+    ("Ctrl-Left", Command::WordLeft),
+    ("Ctrl-Right", Command::WordRight),
+    ("Home", Command::LineStart),
+    ("End", Command::LineEnd),
+    ("Ctrl-]", Command::MatchBracket),
+    ("PageUp", Command::PageUp),
+    ("PageDown", Command::PageDown),
+    ("Ctrl-E", Command::Execute),
+    ("Ctrl-Z", Command::Suspend),
+    ("Alt-v", Command::Visual),
+    ("Alt-V", Command::VisualLine),
+    ("Alt-b", Command::VisualBlock),
+    ("Alt-i", Command::BlockInsert),
+    ("Alt-A", Command::BlockAppend),
+    ("Alt-u", Command::UpperCase),
+    ("Alt-l", Command::LowerCase),
+    ("Ctrl-X", Command::Cut),
+    ("Ctrl-C", Command::Copy),
+    ("Ctrl-V", Command::Paste),
+    ("Ctrl-R", Command::Register),
+    ("Ctrl-D", Command::AddCursorNext),
+    ("Alt-Up", Command::AddCursorAbove),
+    ("Alt-Down", Command::AddCursorBelow),
+    ("Alt-a", Command::CursorsAtMatches),
+];
+
+/// Commands listed in the help line, in order.
+const HELP_COMMANDS: &[Command] = &[Command::Save, Command::Quit, Command::Find, Command::Execute];
+
+impl Command {
+    pub fn name(self) -> &'static str {
+        COMMANDS.iter().find(|(c, _)| *c == self).map(|(_, n)| *n).unwrap()
+    }
+
+    pub fn from_name(name: &str) -> Option<Command> {
+        COMMANDS.iter().find(|(_, n)| *n == name).map(|(c, _)| *c)
+    }
+
+    /// Moves the cursor and nothing else; with Shift held it extends the selection.
+    pub fn is_motion(self) -> bool {
+        matches!(self, Command::MoveLeft | Command::MoveRight | Command::MoveUp | Command::MoveDown
+            | Command::WordLeft | Command::WordRight | Command::LineStart | Command::LineEnd
+            | Command::MatchBracket | Command::PageUp | Command::PageDown)
+    }
+
+    /// Works on the selection, or leaves it alone; any other command ends it.
+    pub fn keeps_selection(self) -> bool {
+        self.is_motion() || matches!(self, Command::DeleteBackward | Command::DeleteForward | Command::NewLine
+            | Command::InsertTab | Command::Indent | Command::Outdent | Command::Visual | Command::VisualLine
+            | Command::VisualBlock | Command::BlockInsert | Command::BlockAppend | Command::UpperCase
+            | Command::LowerCase | Command::Cut | Command::Copy | Command::Paste | Command::Register
+            | Command::Redraw | Command::Save | Command::Suspend)
+    }
+
+    /// Leaves the register picked for the next cut, copy, paste or delete pending.
+    pub fn keeps_register(self) -> bool {
+        self.is_motion() || matches!(self, Command::DeleteBackward | Command::DeleteForward | Command::Visual
+            | Command::VisualLine | Command::VisualBlock | Command::Cut | Command::Copy | Command::Paste
+            | Command::Register)
+    }
+
+    /// Works at every cursor, adds cursors or leaves them alone; any other
+    /// command goes back to the main cursor alone.
+    pub fn keeps_cursors(self) -> bool {
+        matches!(self, Command::MoveLeft | Command::MoveRight | Command::MoveUp | Command::MoveDown
+            | Command::WordLeft | Command::WordRight | Command::LineStart | Command::LineEnd
+            | Command::MatchBracket | Command::NewLine | Command::DeleteBackward | Command::DeleteForward
+            | Command::InsertTab | Command::Indent | Command::Outdent | Command::AddCursorNext
+            | Command::AddCursorAbove | Command::AddCursorBelow | Command::CursorsAtMatches
+            | Command::Redraw | Command::Save | Command::Suspend)
+    }
+}
+
+pub enum Lookup {
+    Command(Command),
+    /// The keys so far start a longer binding.
+    Prefix,
+    Unbound,
+}
+
+/// Maps key sequences, like `Ctrl-X Ctrl-S`, to commands.
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyEvent>, Command)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Keymap { bindings: Vec::new() };
+        for (keys, command) in DEFAULT_BINDINGS {
+            keymap.bind(parse_keys(keys).unwrap(), *command);
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// The default bindings, changed by the `<keys> = <command>` lines of
+    /// the file at `path`. A missing file is not an error.
+    pub fn load(path: &Path) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(keymap),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |msg: String| format!("{}:{}: {}", path.display(), n + 1, msg);
+            let (keys, command) = line.split_once('=').ok_or_else(|| err("expected '<keys> = <command>'".to_string()))?;
+            let keys = parse_keys(keys.trim()).map_err(err)?;
+            match command.trim() {
+                "none" => keymap.unbind(&keys),
+                name => match Command::from_name(name) {
+                    Some(command) => keymap.bind(keys, command),
+                    None => return Err(err(format!("unknown command '{}'", name))),
+                },
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Bind `keys`, dropping bindings it would shadow or that would shadow it.
+    pub fn bind(&mut self, keys: Vec<KeyEvent>, command: Command) {
+        self.bindings.retain(|(k, _)| !k.starts_with(&keys) && !keys.starts_with(k));
+        self.bindings.push((keys, command));
+    }
+
+    pub fn unbind(&mut self, keys: &[KeyEvent]) {
+        self.bindings.retain(|(k, _)| k != keys);
+    }
+
+    pub fn lookup(&self, keys: &[KeyEvent]) -> Lookup {
+        let mut prefix = false;
+        for (k, command) in &self.bindings {
+            if k == keys {
+                return Lookup::Command(*command);
+            }
+            prefix |= k.starts_with(keys);
+        }
+        if prefix { Lookup::Prefix } else { Lookup::Unbound }
+    }
+
+    /// The first key sequence bound to `command`, as it is written in key maps.
+    pub fn keys_for(&self, command: Command) -> Option<String> {
+        self.bindings.iter().find(|(_, c)| *c == command).map(|(keys, _)| keys_to_string(keys))
+    }
+
+    pub fn help_text(&self) -> String {
+        let help: Vec<String> = HELP_COMMANDS.iter()
+            .filter_map(|c| self.keys_for(*c).map(|keys| format!("{} = {}", keys, c.name())))
+            .collect();
+        format!("HELP: {}", help.join(" | "))
+    }
+}
+
+/// Whether a key not bound to anything types itself.
+pub fn is_self_insert(key: &KeyEvent) -> bool {
+    matches!(key.code, KeyCode::Char(_)) && !key.mods.intersects(Modifiers::CTRL | Modifiers::ALT)
+}
+
+pub fn parse_keys(s: &str) -> Result<Vec<KeyEvent>, String> {
+    let keys = s.split_whitespace().map(|k| k.parse()).collect::<Result<Vec<KeyEvent>, String>>()?;
+    if keys.is_empty() {
+        return Err("no keys given".to_string());
+    }
+    Ok(keys)
+}
+
+pub fn keys_to_string(keys: &[KeyEvent]) -> String {
+    keys.iter().map(|k| k.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sequence() {
+        let mut keymap = Keymap::default();
+        keymap.bind(parse_keys("Ctrl-X Ctrl-S").unwrap(), Command::Save);
+        assert!(matches!(keymap.lookup(&[KeyEvent::ctrl('x')]), Lookup::Prefix));
+        assert!(matches!(keymap.lookup(&parse_keys("Ctrl-X Ctrl-S").unwrap()), Lookup::Command(Command::Save)));
+        assert!(matches!(keymap.lookup(&parse_keys("Ctrl-X Ctrl-C").unwrap()), Lookup::Unbound));
+        assert_eq!("HELP: Ctrl-S = save | Ctrl-Q = quit | Ctrl-F = find | Ctrl-E = command", keymap.help_text());
+
+        keymap.unbind(&[KeyEvent::ctrl('s')]);
+        assert_eq!(Some("Ctrl-X Ctrl-S".to_string()), keymap.keys_for(Command::Save));
+    }
+}
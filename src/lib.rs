@@ -1,6 +1,17 @@
 pub mod editor;
 pub mod config;
 mod key;
+mod event;
+mod keymap;
+mod terminal;
 mod util;
 mod highlight;
-mod syntax;
\ No newline at end of file
+mod syntax;
+mod editorconfig;
+pub mod cli;
+mod fileio;
+mod swap;
+mod diff;
+mod watch;
+mod clipboard;
+mod registers;
\ No newline at end of file
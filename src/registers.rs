@@ -0,0 +1,186 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::clipboard::{Clip, ClipKind};
+use crate::{config, swap};
+
+const MAGIC: &[u8] = b"my_vim registers\n";
+
+/// Vim style registers: the unnamed one `"` cut, copy and paste use by
+/// default, `a` to `z` (`A` to `Z` append to them), the last copy in `0`,
+/// the last cuts and deletions in `1` to `9`, and `_` that forgets
+/// whatever goes in.
+#[derive(Default, PartialEq, Debug)]
+pub struct Registers {
+    regs: Vec<(char, Clip)>,
+}
+
+impl Registers {
+    pub fn is_name(name: char) -> bool {
+        name.is_ascii_alphanumeric() || name == '"' || name == '_'
+    }
+
+    pub fn get(&self, name: char) -> Option<&Clip> {
+        let name = name.to_ascii_lowercase();
+        self.regs.iter().find(|(n, _)| *n == name).map(|(_, clip)| clip)
+    }
+
+    /// Text cut (`cut`) or copied into register `name`.
+    pub fn store(&mut self, name: char, clip: Clip, cut: bool) {
+        match name {
+            '_' => return,
+            '"' if cut => self.push_history(clip.clone()),
+            '"' => self.set('0', clip.clone()),
+            'A'..='Z' => {
+                let name = name.to_ascii_lowercase();
+                let clip = match self.get(name) {
+                    Some(old) => {
+                        let kind = match (old.kind, clip.kind) {
+                            (ClipKind::Lines, _) | (_, ClipKind::Lines) => ClipKind::Lines,
+                            (ClipKind::Block, _) | (_, ClipKind::Block) => ClipKind::Block,
+                            _ => ClipKind::Chars,
+                        };
+                        let mut text = old.text.clone();
+                        if kind != ClipKind::Chars {
+                            text.push(b'\n');
+                        }
+                        text.extend_from_slice(&clip.text);
+                        Clip { text, kind }
+                    }
+                    None => clip,
+                };
+                self.set(name, clip.clone());
+                self.set('"', clip);
+                return;
+            }
+            name => self.set(name, clip.clone()),
+        }
+        self.set('"', clip);
+    }
+
+    /// Text deleted rather than cut: kept in `1` to `9` only.
+    pub fn store_deleted(&mut self, clip: Clip) {
+        self.push_history(clip);
+    }
+
+    /// The registers holding something, in name order.
+    pub fn list(&self) -> Vec<(char, &Clip)> {
+        let mut list: Vec<(char, &Clip)> = self.regs.iter().map(|(n, clip)| (*n, clip)).collect();
+        list.sort_by_key(|(n, _)| *n);
+        list
+    }
+
+    fn set(&mut self, name: char, clip: Clip) {
+        match self.regs.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, old)) => *old = clip,
+            None => self.regs.push((name, clip)),
+        }
+    }
+
+    /* `1` takes the clip, what was there moves on to `2` and so on, `9` falls off */
+    fn push_history(&mut self, clip: Clip) {
+        self.regs.retain(|(n, _)| *n != '9');
+        for (n, _) in self.regs.iter_mut().filter(|(n, _)| ('1'..='8').contains(n)) {
+            *n = (*n as u8 + 1) as char;
+        }
+        self.set('1', clip);
+    }
+
+    /// Each register as a `<name> <kind> <length>` line, the kind `c` for
+    /// characters, `l` for whole lines or `b` for a block, then its text
+    /// and a line break.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for (name, clip) in self.list() {
+            let kind = match clip.kind {
+                ClipKind::Chars => 'c',
+                ClipKind::Lines => 'l',
+                ClipKind::Block => 'b',
+            };
+            bytes.extend_from_slice(format!("{} {} {}\n", name, kind, clip.text.len()).as_bytes());
+            bytes.extend_from_slice(&clip.text);
+            bytes.push(b'\n');
+        }
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Option<Registers> {
+        bytes = bytes.strip_prefix(MAGIC)?;
+        let mut registers = Registers::default();
+        while !bytes.is_empty() {
+            let end = bytes.iter().position(|c| *c == b'\n')?;
+            let header = std::str::from_utf8(&bytes[..end]).ok()?;
+            let mut fields = header.split(' ');
+            let (name, kind, len) = (fields.next()?, fields.next()?, fields.next()?.parse::<usize>().ok()?);
+            let name = name.parse::<char>().ok().filter(|n| Registers::is_name(*n))?;
+            let kind = match kind {
+                "c" => ClipKind::Chars,
+                "l" => ClipKind::Lines,
+                "b" => ClipKind::Block,
+                _ => return None,
+            };
+            let text = bytes.get(end + 1..end + 1 + len)?.to_vec();
+            registers.set(name, Clip { text, kind });
+            bytes = bytes.get(end + 2 + len..)?;
+        }
+        Some(registers)
+    }
+
+    /// The registers kept from the last session, none when there is no file.
+    pub fn load(path: &Path) -> io::Result<Registers> {
+        match fs::read(path) {
+            Ok(bytes) => Registers::from_bytes(&bytes)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a registers file")),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Registers::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        swap::write_private(path, &self.to_bytes())
+    }
+}
+
+/// Where registers are kept between sessions.
+pub fn registers_path() -> Option<PathBuf> {
+    Some(config::state_dir()?.join("registers"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn clip(text: &str, kind: ClipKind) -> Clip {
+        Clip { text: text.as_bytes().to_vec(), kind }
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut r = Registers::default();
+        r.store('"', clip("one", ClipKind::Chars), false);
+        r.store('"', clip("two", ClipKind::Lines), true);
+        r.store('"', clip("three", ClipKind::Chars), true);
+        assert_eq!(Some(&clip("one", ClipKind::Chars)), r.get('0'));
+        assert_eq!(Some(&clip("three", ClipKind::Chars)), r.get('1'));
+        assert_eq!(Some(&clip("two", ClipKind::Lines)), r.get('2'));
+        assert_eq!(Some(&clip("three", ClipKind::Chars)), r.get('"'));
+
+        r.store('a', clip("x", ClipKind::Chars), false);
+        r.store('A', clip("y", ClipKind::Lines), false);
+        assert_eq!(Some(&clip("x\ny", ClipKind::Lines)), r.get('a'));
+        r.store('_', clip("gone", ClipKind::Chars), true);
+        assert_eq!(Some(&clip("x\ny", ClipKind::Lines)), r.get('"'));
+
+        for i in 0..10 {
+            r.store_deleted(clip(&i.to_string(), ClipKind::Chars));
+        }
+        assert_eq!(Some(&clip("9", ClipKind::Chars)), r.get('1'));
+        assert_eq!(Some(&clip("1", ClipKind::Chars)), r.get('9'));
+
+        assert_eq!(Some(r.list().len()), Registers::from_bytes(&r.to_bytes()).map(|r| r.list().len()));
+        assert_eq!(Some(&clip("x\ny", ClipKind::Lines)), Registers::from_bytes(&r.to_bytes()).unwrap().get('a'));
+        r.store('b', clip("1\n2", ClipKind::Block), false);
+        assert_eq!(Some(&clip("1\n2", ClipKind::Block)), Registers::from_bytes(&r.to_bytes()).unwrap().get('b'));
+        assert!(Registers::from_bytes(b"my_vim registers\na c 10\nshort\n").is_none());
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{self, Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+use crate::{config, fileio, util};
+
+const MAGIC: &str = "my_vim swap";
+
+/// The unsaved text of a buffer, kept in case the editor dies.
+pub struct Swap {
+    /// The editor that wrote it.
+    pub pid: u32,
+    pub cx: u32,
+    pub cy: u32,
+    pub modified: SystemTime,
+    /// As it would have been saved.
+    pub text: Vec<u8>,
+}
+
+impl Swap {
+    /// Whether the editor that wrote it is still running, editing the file now.
+    pub fn is_running(&self) -> bool {
+        self.pid != process::id() && unsafe { libc::kill(self.pid as libc::pid_t, 0) } == 0
+    }
+}
+
+/// The swap file for `file`, named after its absolute path with `/` turned
+/// into `%`, in the `swap` directory under the state directory.
+pub fn swap_path(file: &Path) -> Option<PathBuf> {
+    let file = path::absolute(file).ok()?;
+    let name = file.to_string_lossy().replace('/', "%");
+    Some(config::state_dir()?.join("swap").join(format!("{}.swp", name)))
+}
+
+/// Where the text of a buffer goes when the editor dies without a swap file
+/// to put it in: a new file in the `recovery` directory under the state directory.
+pub fn recovery_path() -> Option<PathBuf> {
+    let name = format!("{}.{}.txt", util::local_timestamp(), process::id());
+    Some(config::state_dir()?.join("recovery").join(name))
+}
+
+pub fn write(path: &Path, cx: u32, cy: u32, text: &[u8]) -> io::Result<()> {
+    let mut content = format!("{} pid={} cursor={},{}\n", MAGIC, process::id(), cy, cx).into_bytes();
+    content.extend_from_slice(text);
+    write_private(path, &content)
+}
+
+pub fn write_recovery(path: &Path, text: &[u8]) -> io::Result<()> {
+    write_private(path, text)
+}
+
+/// Write `content` where only the user can read it, creating its directory.
+pub fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        // the text may be private
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    fileio::write_atomic(path, content)
+}
+
+pub fn read(path: &Path) -> io::Result<Swap> {
+    let content = fs::read(path)?;
+    let modified = fs::metadata(path)?.modified()?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a swap file");
+    let end = content.iter().position(|c| *c == b'\n').ok_or_else(invalid)?;
+    let header = std::str::from_utf8(&content[..end]).map_err(|_| invalid())?;
+
+    let fields = header.strip_prefix(MAGIC).ok_or_else(invalid)?;
+    let (mut pid, mut cursor) = (None, None);
+    for field in fields.split_whitespace() {
+        match field.split_once('=') {
+            Some(("pid", v)) => pid = v.parse().ok(),
+            Some(("cursor", v)) => cursor = v.split_once(',').and_then(|(y, x)| Some((y.parse().ok()?, x.parse().ok()?))),
+            _ => {}
+        }
+    }
+    let (cy, cx) = cursor.unwrap_or((0, 0));
+    Ok(Swap { pid: pid.ok_or_else(invalid)?, cx, cy, modified, text: content[end + 1..].to_vec() })
+}
@@ -1,14 +1,22 @@
 use lazy_static::lazy_static;
+use crate::config::Value;
 use crate::highlight::Highlight;
 use crate::util;
 
 pub struct Syntax {
     pub file_type: &'static str,
+    /// Extensions starting with `.`, or whole file names like `Makefile`.
     pub file_math: Vec<&'static str>,
     single_comment_start: &'static str,
     keyword: Vec<&'static str>,
     multi_comment_start: &'static str,
     multi_comment_end: &'static str,
+    /// Characters that, ending a line, indent the next one a level deeper.
+    indent_after: &'static str,
+    /// Closing brackets that, typed first on a line, take it a level back out.
+    dedent_on: &'static str,
+    /// Settings files of this type start with, before the config file and `.editorconfig`.
+    pub settings: Vec<(&'static str, Value)>,
 }
 
 lazy_static! {
@@ -23,12 +31,61 @@ lazy_static! {
                 "void|"],
             multi_comment_start: "/*",
             multi_comment_end: "*/",
-        }
+            indent_after: "{([",
+            dedent_on: "})]",
+            settings: vec![],
+        },
+        Syntax {
+            file_type: "make",
+            file_math: vec!["Makefile", "makefile", "GNUmakefile", ".mk"],
+            single_comment_start: "#",
+            keyword: vec!["ifeq", "ifneq", "ifdef", "ifndef", "else", "endif", "include",
+                "define", "endef", "export", "unexport", "override", "vpath"],
+            multi_comment_start: "",
+            multi_comment_end: "",
+            // a rule's recipe goes on the lines after it
+            indent_after: ":",
+            dedent_on: "",
+            // recipes must start with a tab
+            settings: vec![("expand_tabs", Value::Bool(false)), ("tab_width", Value::Int(8))],
+        },
+        Syntax {
+            file_type: "go",
+            file_math: vec![".go"],
+            single_comment_start: "//",
+            keyword: vec!["break", "case", "chan", "const", "continue", "default", "defer", "else",
+                "fallthrough", "for", "func", "go", "goto", "if", "import", "interface", "map",
+                "package", "range", "return", "select", "struct", "switch", "type", "var",
+                "bool|", "byte|", "error|", "int|", "int64|", "float64|", "rune|", "string|",
+                "uint|", "nil|", "true|", "false|"],
+            multi_comment_start: "/*",
+            multi_comment_end: "*/",
+            indent_after: "{([",
+            dedent_on: "})]",
+            // gofmt indents with tabs
+            settings: vec![("expand_tabs", Value::Bool(false)), ("tab_width", Value::Int(4))],
+        },
+        Syntax {
+            file_type: "python",
+            file_math: vec![".py", ".pyw"],
+            single_comment_start: "#",
+            keyword: vec!["and", "as", "assert", "break", "class", "continue", "def", "del", "elif",
+                "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+                "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+                "with", "yield",
+                "None|", "True|", "False|", "self|", "int|", "str|", "list|", "dict|"],
+            multi_comment_start: "",
+            multi_comment_end: "",
+            indent_after: ":([{",
+            dedent_on: ")]}",
+            // PEP 8
+            settings: vec![("expand_tabs", Value::Bool(true)), ("tab_width", Value::Int(4))],
+        },
     ];
 }
 
 impl Syntax {
-    pub fn syntax_highlight(&self, line: &Vec<u8>) -> Vec<Highlight> {
+    pub fn syntax_highlight(&self, line: &[u8]) -> Vec<Highlight> {
         let mut r = Vec::new();
 
         let mut i = 0;
@@ -37,7 +94,8 @@ impl Syntax {
         'line: while i < line.len() {
             let c = line[i];
             // single comment
-            if in_string == 0 && i + 1 < line.len() && &line[i..i + 2] == self.single_comment_start.as_bytes() {
+            if in_string == 0 && !self.single_comment_start.is_empty()
+                && line[i..].starts_with(self.single_comment_start.as_bytes()) {
                 while i < line.len() {
                     r.push(Highlight::Comment);
                     i += 1;
@@ -48,7 +106,7 @@ impl Syntax {
             // string highlight
             if in_string > 0 {
                 r.push(Highlight::String);
-                if c == '\\' as u8 && i < line.len() - 1 {
+                if c == b'\\' && i < line.len() - 1 {
                     r.push(Highlight::String);
                     i += 2;
                     continue;
@@ -58,7 +116,7 @@ impl Syntax {
                 prev_sep = true;
                 continue;
             } else {
-                if c == '"' as u8 || c == '\'' as u8 {
+                if c == b'"' || c == b'\'' {
                     in_string = c;
                     r.push(Highlight::String);
                     i += 1;
@@ -69,7 +127,7 @@ impl Syntax {
 
             // digit highlight
             if (c.is_ascii_digit() && (prev_sep || r[i - 1] == Highlight::Number))
-                || (c == '.' as u8 && i > 0 && r[i - 1] == Highlight::Number) {
+                || (c == b'.' && i > 0 && r[i - 1] == Highlight::Number) {
                 r.push(Highlight::Number);
                 i += 1;
                 prev_sep = false;
@@ -81,19 +139,18 @@ impl Syntax {
                 for keyword in self.keyword.iter() {
                     let mut len = keyword.len();
                     let mut k2 = false;
-                    if keyword.as_bytes()[len - 1] == '|' as u8 {
+                    if keyword.as_bytes()[len - 1] == b'|' {
                         len -= 1;
                         k2 = true;
                     }
 
-                    if i + len == line.len() || (i + len < line.len() && util::is_separator(line[i + len])) {
-                        if &line[i..i + len] == keyword[0..len].as_bytes() {
-                            for _ in 0..len {
-                                if k2 { r.push(Highlight::Keyword2); } else { r.push(Highlight::Keyword1); }
-                            }
-                            i += len;
-                            continue 'line;
+                    if (i + len == line.len() || (i + len < line.len() && util::is_separator(line[i + len])))
+                        && line[i..].starts_with(&keyword.as_bytes()[0..len]) {
+                        for _ in 0..len {
+                            if k2 { r.push(Highlight::Keyword2); } else { r.push(Highlight::Keyword1); }
                         }
+                        i += len;
+                        continue 'line;
                     }
                 }
             }
@@ -106,22 +163,29 @@ impl Syntax {
         r
     }
 
-    pub fn is_multi_comment_start(&self, line: &Vec<u8>) -> bool {
-        if line.len() >= self.multi_comment_start.len() &&
-            &line[0..self.multi_comment_start.len()] == self.multi_comment_start.as_bytes() {
-            true
-        } else {
-            false
-        }
+    pub fn is_multi_comment_start(&self, line: &[u8]) -> bool {
+        !self.multi_comment_start.is_empty() && line.starts_with(self.multi_comment_start.as_bytes())
     }
 
-    pub fn is_multi_comment_end(&self, line: &Vec<u8>) -> bool {
-        if line.len() >= self.multi_comment_end.len() &&
-            &line[line.len() - self.multi_comment_end.len()..] == self.multi_comment_end.as_bytes() {
-            true
-        } else {
-            false
-        }
+    pub fn is_multi_comment_end(&self, line: &[u8]) -> bool {
+        !self.multi_comment_end.is_empty() && line.ends_with(self.multi_comment_end.as_bytes())
+    }
+
+    /// Whether a line ending in `c` opens a block the next line goes into.
+    pub fn indents_after(&self, c: u8) -> bool {
+        self.indent_after.as_bytes().contains(&c)
+    }
+
+    /// Whether typing `c` first on a line closes the block it is in.
+    pub fn dedents_on(&self, c: u8) -> bool {
+        self.dedent_on.as_bytes().contains(&c)
+    }
+
+    /// Whether a file called `file_name` is of this type.
+    pub fn matches(&self, file_name: &str) -> bool {
+        let base = file_name.rsplit('/').next().unwrap_or(file_name);
+        let ext = util::get_file_type(base);
+        self.file_math.iter().any(|m| if m.starts_with('.') { *m == ext } else { *m == base })
     }
 }
 
@@ -132,7 +196,27 @@ mod test {
     #[test]
     fn test_keyword() {
         let s = "*/";
-        assert_eq!(true, HLDB[0].is_multi_comment_end(&s.as_bytes().to_vec()));
+        assert!(HLDB[0].is_multi_comment_end(s.as_bytes()));
         // HLDB[0].syntax_highlight(&s.as_bytes().to_vec());
     }
+
+    #[test]
+    fn test_file_match() {
+        let find = |name: &str| HLDB.iter().find(|s| s.matches(name)).map(|s| s.file_type);
+        assert_eq!(Some("c"), find("src/main.c"));
+        assert_eq!(Some("make"), find("build/Makefile"));
+        assert_eq!(Some("make"), find("rules.mk"));
+        assert_eq!(Some("python"), find("setup.py"));
+        assert_eq!(None, find("Makefile.bak"));
+        assert_eq!(None, find("a.b/README"));
+        assert!(!HLDB[1].is_multi_comment_start(b"all:"));
+    }
+
+    #[test]
+    fn test_indent_rules() {
+        let python = HLDB.iter().find(|s| s.file_type == "python").unwrap();
+        assert!(python.indents_after(b':') && python.indents_after(b'('));
+        assert!(!HLDB[0].indents_after(b':') && HLDB[0].indents_after(b'{'));
+        assert!(HLDB[0].dedents_on(b'}') && !HLDB[0].dedents_on(b'{'));
+    }
 }
\ No newline at end of file
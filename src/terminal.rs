@@ -0,0 +1,137 @@
+use std::fs::File;
+use std::io;
+use std::io::{stdout, Write};
+use std::os::unix::io::AsRawFd;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+
+/// Terminal modes switched on while the editor runs, with the sequences
+/// turning them off again.
+const MODES: &[(&str, &str)] = &[
+    // kitty keyboard protocol, disambiguate escape codes
+    ("\x1b[>1u", "\x1b[<u"),
+    // xterm modifyOtherKeys
+    ("\x1b[>4;1m", "\x1b[>4m"),
+    // bracketed paste
+    ("\x1b[?2004h", "\x1b[?2004l"),
+    // mouse buttons, drags and the wheel, reported in SGR form
+    ("\x1b[?1002h\x1b[?1006h", "\x1b[?1006l\x1b[?1002l"),
+    // focus in and out reports
+    ("\x1b[?1004h", "\x1b[?1004l"),
+];
+
+/// The terminal settings from before raw mode, while they need putting back.
+static ORIGINAL: Mutex<Option<libc::termios>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+/// On the alternate screen, to leave it again.
+static ALT_SCREEN: AtomicBool = AtomicBool::new(false);
+
+pub struct Terminal {
+    stdout: Box<dyn Write>,
+}
+
+impl Terminal {
+    pub fn new() -> io::Result<Self> {
+        // keys come from the terminal even when the text was piped in
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+            let tty = File::open("/dev/tty")?;
+            if unsafe { libc::dup2(tty.as_raw_fd(), libc::STDIN_FILENO) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        // the panic message goes to a usable terminal, not over the text in raw mode
+        PANIC_HOOK.call_once(|| {
+            let default_hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore();
+                default_hook(info);
+            }));
+        });
+
+        let mut terminal = Terminal { stdout: Box::new(stdout()) };
+        terminal.resume()?;
+        Ok(terminal)
+    }
+
+    /// Writes nowhere and leaves the terminal's modes alone, for tests.
+    #[cfg(test)]
+    pub fn detached() -> Self {
+        Terminal { stdout: Box::new(io::sink()) }
+    }
+
+    /// Raw mode and the editor's terminal modes, again after `restore` or
+    /// after the process was stopped and continued: the shell may have put
+    /// its own settings back meanwhile. The alternate screen is left to
+    /// `set_alt_screen`.
+    pub fn resume(&mut self) -> io::Result<()> {
+        let mut original = ORIGINAL.lock().unwrap_or_else(|e| e.into_inner());
+        let mut termios = match *original {
+            Some(termios) => termios,
+            None => {
+                let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+                if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut termios) } < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                termios
+            }
+        };
+        let saved = termios;
+        unsafe { libc::cfmakeraw(&mut termios) };
+        if unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, &termios) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        *original = Some(saved);
+        drop(original);
+
+        for (enable, _) in MODES {
+            self.stdout.write_all(enable.as_bytes())?;
+        }
+        self.stdout.flush()
+    }
+
+    /// Switch to the alternate screen, or back to the normal one.
+    pub fn set_alt_screen(&mut self, on: bool) -> io::Result<()> {
+        if ALT_SCREEN.swap(on, Ordering::SeqCst) == on {
+            return Ok(());
+        }
+        self.stdout.write_all(if on { b"\x1b[?1049h" } else { b"\x1b[?1049l" })?;
+        self.stdout.flush()
+    }
+}
+
+/// Put the terminal back the way the editor found it: its modes off, the
+/// shell's screen back (or the editor's cleared, without the alternate
+/// screen), the cursor shown, out of raw mode. Does nothing once done, so it
+/// is safe to call on every way out.
+pub fn restore() {
+    let Some(original) = ORIGINAL.lock().unwrap_or_else(|e| e.into_inner()).take() else { return };
+    let mut stdout = stdout();
+    for (_, disable) in MODES.iter().rev() {
+        let _ = stdout.write_all(disable.as_bytes());
+    }
+    if ALT_SCREEN.swap(false, Ordering::SeqCst) {
+        let _ = stdout.write_all(b"\x1b[?1049l");
+    } else {
+        let _ = stdout.write_all(b"\x1b[2J\x1b[H");
+    }
+    let _ = stdout.write_all(b"\x1b[m\x1b[?25h");
+    let _ = stdout.flush();
+    unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, &original) };
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        restore();
+    }
+}
@@ -1,15 +1,77 @@
 use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(test)]
+use std::{env, fs, path::PathBuf, process, sync::atomic::{AtomicU32, Ordering}};
 
 pub fn get_current_time_secs() -> u64 {
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
 }
 
+/// The local time as `YYYYMMDD-HHMMSS`.
+pub fn local_timestamp() -> String {
+    let now = get_current_time_secs() as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe { libc::localtime_r(&now, &mut tm) };
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday, tm.tm_hour, tm.tm_min, tm.tm_sec)
+}
+
+/// A directory of a test's own, removed with what is in it once dropped.
+#[cfg(test)]
+pub struct TempDir {
+    pub path: PathBuf,
+}
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new() -> TempDir {
+        static COUNT: AtomicU32 = AtomicU32::new(0);
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path = env::temp_dir().join(format!("my_vim-test-{}-{}", process::id(), n));
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
 pub fn is_separator(c: u8) -> bool {
-    if c == 32 || c == '\0' as u8 || ",.()+-/*=~%<>[];".contains(c as char) {
-        true
+    c == b' ' || c == b'\0' || ",.()+-/*=~%<>[];".contains(c as char)
+}
+
+pub fn is_word_char(c: u8) -> bool {
+    !is_separator(c) && !c.is_ascii_whitespace()
+}
+
+/// Length of the spaces and tabs `line` starts with.
+pub fn indent_len(line: &[u8]) -> usize {
+    line.iter().take_while(|c| matches!(c, b' ' | b'\t')).count()
+}
+
+/// `line` with its indentation written as spaces, or as tabs `tab_width`
+/// wide and spaces for the rest, keeping its width. None when it is
+/// written that way already.
+pub fn retab(line: &[u8], tab_width: u32, expand_tabs: bool) -> Option<Vec<u8>> {
+    let len = indent_len(line);
+    let width = line[..len].iter().fold(0, |w, c| if *c == b'\t' { w + tab_width - w % tab_width } else { w + 1 });
+    let mut indent = if expand_tabs {
+        vec![b' '; width as usize]
     } else {
-        false
+        [vec![b'\t'; (width / tab_width) as usize], vec![b' '; (width % tab_width) as usize]].concat()
+    };
+    if indent == line[..len] {
+        return None;
     }
+    indent.extend_from_slice(&line[len..]);
+    Some(indent)
+}
+
+/// The first `n` characters of `s`, or all of it when it is shorter.
+pub fn truncate_chars(s: &str, n: usize) -> &str {
+    s.char_indices().nth(n).map_or(s, |(i, _)| &s[..i])
 }
 
 pub fn get_file_type(file_name: &str) -> &str {
@@ -17,4 +79,46 @@ pub fn get_file_type(file_name: &str) -> &str {
         None => "",
         Some(i) => &file_name[i..],
     }
-}
\ No newline at end of file
+}
+
+/// Split text on `\n`, `\r\n` or a lone `\r`. A trailing line break yields a
+/// last empty line, so the result always has one more entry than there are breaks.
+pub fn split_lines(text: &[u8]) -> Vec<&[u8]> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i] == b'\n' || text[i] == b'\r' {
+            lines.push(&text[start..i]);
+            if text[i] == b'\r' && i + 1 < text.len() && text[i + 1] == b'\n' {
+                i += 1;
+            }
+            start = i + 1;
+        }
+        i += 1;
+    }
+    lines.push(&text[start..]);
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retab() {
+        assert_eq!(Some(b"        x".to_vec()), retab(b"\t  \tx", 4, true));
+        assert_eq!(Some(b"\t\t\tx".to_vec()), retab(b"   \t    \tx", 4, false));
+        assert_eq!(Some(b"\t\t x".to_vec()), retab(b"         x", 4, false));
+        assert_eq!(None, retab(b"\tx", 4, false));
+        assert_eq!(None, retab(b"x\t", 4, true));
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!("ab", truncate_chars("abc", 2));
+        assert_eq!("abc", truncate_chars("abc", 5));
+        assert_eq!("é ü", truncate_chars("é üx", 3));
+        assert_eq!("", truncate_chars("é", 0));
+    }
+}
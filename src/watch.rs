@@ -0,0 +1,79 @@
+use std::ffi::OsString;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+
+/// Notices other programs writing a file as it happens, by watching its
+/// directory with inotify: editors and tools that save by renaming a new
+/// file over the old one would leave a watch on the file itself behind.
+/// Only on Linux; elsewhere the periodic disk check has to do.
+pub struct FileWatcher {
+    fd: RawFd,
+    path: PathBuf,
+    name: OsString,
+}
+
+impl FileWatcher {
+    #[cfg(target_os = "linux")]
+    pub fn new(path: &Path) -> Option<FileWatcher> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = path.file_name()?.to_owned();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_CREATE | libc::IN_DELETE | libc::IN_MODIFY;
+        if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), mask) } < 0 {
+            unsafe { libc::close(fd) };
+            return None;
+        }
+        Some(FileWatcher { fd, path: path.to_path_buf(), name })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn new(_path: &Path) -> Option<FileWatcher> {
+        None
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the notifications waiting, whether any was about the file.
+    pub fn changed(&mut self) -> bool {
+        // struct inotify_event: wd, mask, cookie, len, then len bytes of name
+        const HEADER: usize = 16;
+        let mut buf = [0_u8; 4096];
+        let mut changed = false;
+        loop {
+            let n = unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+            if n <= 0 {
+                return changed;
+            }
+            let mut events = &buf[..n as usize];
+            while events.len() >= HEADER {
+                let len = u32::from_ne_bytes(events[12..16].try_into().unwrap()) as usize;
+                let name = &events[HEADER..HEADER + len];
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(len)];
+                changed |= name == self.name.as_encoded_bytes();
+                events = &events[HEADER + len..];
+            }
+        }
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}